@@ -3,6 +3,8 @@ use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, ToTokens};
 use syn::*;
 
+mod pattern;
+
 // We allow the user to use tuples, Slot, Bind<_>, AppliedId and "user-defined types" in their enum variants.
 // user-defined types will be understood as slot-independent constants, and ignored by the system.
 
@@ -78,6 +80,13 @@ pub fn define_language(input: TokenStream1) -> TokenStream1 {
         .iter()
         .map(|x| produce_weak_shape_inplace(&name, x))
         .collect();
+    let pattern_op_fns: Vec<TokenStream2> = ie
+        .variants
+        .iter()
+        .zip(&str_names)
+        .filter_map(|(x, n)| produce_pattern_op(&name, n, x))
+        .collect();
+    let pattern_const_fn = produce_pattern_const(&ie, &str_names);
 
     quote! {
         #[derive(PartialEq, Eq, Hash, Clone, Debug, PartialOrd, Ord)]
@@ -157,11 +166,29 @@ pub fn define_language(input: TokenStream1) -> TokenStream1 {
                 m.0.inverse()
             }
         }
+
+        // used by the `pattern!` macro.
+        impl #name {
+            #(#pattern_op_fns)*
+            #pattern_const_fn
+        }
     }
     .to_token_stream()
     .into()
 }
 
+/// Builds a `Pattern<L>` from a pattern string at compile time, e.g. `pattern!(Rise, "(app (lam $x ?b) ?t)")`.
+///
+/// The syntax is the same as for `Pattern::parse`.
+/// The operators and their arities are checked against the language, which has to be defined by `define_language!`,
+/// so a typo is a compile error instead of a panic at runtime.
+/// Bare identifiers are checked as well, unless the language has literal variants (e.g. numbers or symbols):
+/// those are converted at runtime, using `Language::from_syntax`.
+#[proc_macro]
+pub fn pattern(input: TokenStream1) -> TokenStream1 {
+    pattern::pattern(input.into()).into()
+}
+
 fn produce_all_slot_occurrences_mut(name: &Ident, v: &Variant) -> TokenStream2 {
     let variant_name = &v.ident;
     let n = v.fields.len();
@@ -367,3 +394,110 @@ fn produce_weak_shape_inplace(name: &Ident, v: &Variant) -> TokenStream2 {
         }
     }
 }
+
+// For the `pattern!` macro, every variant with an operator name gets a constructor `__pattern_op_<name>`.
+// Its arguments are the slots and children in the order of the syntax, e.g. `(lam $x ?b)` becomes `__pattern_op_lam(slot, child)`.
+// Variants containing other types (which can't be written in patterns) don't get a constructor.
+fn produce_pattern_op(name: &Ident, e: &Option<Expr>, v: &Variant) -> Option<TokenStream2> {
+    let Some(Expr::Lit(ExprLit {
+        lit: Lit::Str(op), ..
+    })) = e
+    else {
+        return None;
+    };
+    let variant_name = &v.ident;
+    let fn_name = pattern_op_ident(&op.value(), proc_macro2::Span::call_site());
+
+    let mut args = Vec::new();
+    let fields = v
+        .fields
+        .iter()
+        .map(|x| produce_pattern_arg(&x.ty, &mut args))
+        .collect::<Option<Vec<TokenStream2>>>()?;
+    let (arg_names, arg_types): (Vec<Ident>, Vec<TokenStream2>) = args.into_iter().unzip();
+    Some(quote! {
+        #[doc(hidden)]
+        pub fn #fn_name(#(#arg_names: #arg_types),*) -> Self {
+            #name::#variant_name(#(#fields),*)
+        }
+    })
+}
+
+// For the `pattern!` macro, `__pattern_const(s)` tells at compile time whether the bare identifier `s` is a constant of the language.
+// That is the case if `s` is a nullary operator, or if the language has variants without an operator name (like numbers or symbols),
+// which can't be checked before `from_syntax` runs.
+fn produce_pattern_const(ie: &ItemEnum, str_names: &[Option<Expr>]) -> TokenStream2 {
+    let has_literals = str_names.iter().any(|n| n.is_none());
+    let nullary_ops: Vec<String> = ie
+        .variants
+        .iter()
+        .zip(str_names)
+        .filter(|(v, _)| v.fields.is_empty())
+        .filter_map(|(_, n)| match n {
+            Some(Expr::Lit(ExprLit {
+                lit: Lit::Str(op), ..
+            })) => Some(op.value()),
+            _ => None,
+        })
+        .collect();
+    quote! {
+        #[doc(hidden)]
+        pub const fn __pattern_const(s: &str) -> bool {
+            const fn eq(a: &[u8], b: &[u8]) -> bool {
+                if a.len() != b.len() {
+                    return false;
+                }
+                let mut i = 0;
+                while i < a.len() {
+                    if a[i] != b[i] {
+                        return false;
+                    }
+                    i += 1;
+                }
+                true
+            }
+            #has_literals #(|| eq(s.as_bytes(), #nullary_ops.as_bytes()))*
+        }
+    }
+}
+
+// Adds the arguments needed to build a field of type `ty` to `args`, and returns the expression building the field.
+fn produce_pattern_arg(ty: &Type, args: &mut Vec<(Ident, TokenStream2)>) -> Option<TokenStream2> {
+    let Type::Path(p) = ty else { return None };
+    let seg = p.path.segments.last()?;
+    let mut arg = |ty: TokenStream2| {
+        let a = Ident::new(&format!("a{}", args.len()), proc_macro2::Span::call_site());
+        args.push((a.clone(), ty));
+        quote! { #a }
+    };
+    match &*seg.ident.to_string() {
+        "Slot" => Some(arg(quote! { Slot })),
+        "AppliedId" => Some(arg(quote! { AppliedId })),
+        "Bind" => {
+            let PathArguments::AngleBracketed(generics) = &seg.arguments else {
+                return None;
+            };
+            let Some(GenericArgument::Type(elem)) = generics.args.first() else {
+                return None;
+            };
+            let slot = arg(quote! { Slot });
+            let elem = produce_pattern_arg(elem, args)?;
+            Some(quote! { Bind { slot: #slot, elem: #elem } })
+        }
+        _ => None,
+    }
+}
+
+// The name of the constructor of the operator `op`, see `produce_pattern_op`.
+// Characters which can't occur in identifiers are replaced by their code point.
+pub(crate) fn pattern_op_ident(op: &str, span: proc_macro2::Span) -> Ident {
+    let mut s = String::from("__pattern_op_");
+    for c in op.chars() {
+        if c.is_ascii_alphanumeric() {
+            s.push(c);
+        } else {
+            s.push_str(&format!("_{:x}_", c as u32));
+        }
+    }
+    Ident::new(&s, span)
+}
@@ -0,0 +1,305 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::*;
+
+use crate::pattern_op_ident;
+
+// `pattern!(Lang, "...")`
+struct Input {
+    lang: Type,
+    pattern: LitStr,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lang = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let pattern = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Input { lang, pattern })
+    }
+}
+
+pub(crate) fn pattern(input: TokenStream2) -> TokenStream2 {
+    let input: Input = match parse2(input) {
+        Ok(x) => x,
+        Err(e) => return e.to_compile_error(),
+    };
+    let span = input.pattern.span();
+    let pat = tokenize(&input.pattern.value()).and_then(|tok| match parse_pattern(&tok)? {
+        (pat, []) => Ok(pat),
+        (_, rest) => Err(format!("unexpected tokens after the pattern: {rest:?}")),
+    });
+    match pat {
+        Ok(pat) => pat.to_tokens(&input.lang, span),
+        Err(e) => Error::new(span, format!("invalid pattern: {e}")).to_compile_error(),
+    }
+}
+
+// The tokens of a pattern string, like in `Pattern::parse`.
+#[derive(Debug)]
+enum Tok {
+    Slot(String),  // $42
+    Ident(String), // map, 15
+    PVar(String),  // ?x
+    ColonEquals,   // :=
+    Comma,         // ,
+    LParen,        // (
+    RParen,        // )
+    LBracket,      // [
+    RBracket,      // ]
+}
+
+fn crop_ident(s: &str) -> std::result::Result<(String, &str), String> {
+    let i = s
+        .find(|c: char| c.is_whitespace() || "()[],".contains(c))
+        .unwrap_or(s.len());
+    match i {
+        0 => Err(format!("expected an identifier at `{s}`")),
+        _ => Ok((s[..i].to_string(), &s[i..])),
+    }
+}
+
+fn tokenize(mut s: &str) -> std::result::Result<Vec<Tok>, String> {
+    let mut tokens = Vec::new();
+    loop {
+        s = s.trim_start();
+        let Some(c) = s.chars().next() else { break };
+        let simple = match c {
+            '(' => Some(Tok::LParen),
+            ')' => Some(Tok::RParen),
+            '[' => Some(Tok::LBracket),
+            ']' => Some(Tok::RBracket),
+            ',' => Some(Tok::Comma),
+            _ => None,
+        };
+        if let Some(t) = simple {
+            tokens.push(t);
+            s = &s[1..];
+        } else if let Some(rest) = s.strip_prefix(":=") {
+            tokens.push(Tok::ColonEquals);
+            s = rest;
+        } else if let Some(rest) = s.strip_prefix('?') {
+            let (x, rest) = crop_ident(rest)?;
+            tokens.push(Tok::PVar(x));
+            s = rest;
+        } else if let Some(rest) = s.strip_prefix('$') {
+            let (x, rest) = crop_ident(rest)?;
+            tokens.push(Tok::Slot(x));
+            s = rest;
+        } else {
+            let (x, rest) = crop_ident(s)?;
+            tokens.push(Tok::Ident(x));
+            s = rest;
+        }
+    }
+    Ok(tokens)
+}
+
+// A parsed pattern, which is turned into code constructing the `Pattern<L>`.
+enum Pat {
+    ENode(String, Vec<Elem>), // (op $x ?a)
+    Const(String),            // 15
+    PVar(String),             // ?x
+    Subst(Box<Pat>, Vec<(Pat, Pat)>),
+    HOVar(String, Vec<Elem>), // ?f[$x, (var $y)]
+    Wildcard,                 // _
+    Or(Vec<Pat>),             // (| p1 p2)
+    OpVar(String, Vec<Pat>),  // (?op ?a ?b)
+}
+
+// A slot or a sub-pattern, either within an e-node or as an argument of a higher-order pattern variable.
+enum Elem {
+    Slot(String),
+    Pat(Pat),
+}
+
+type ParseResult<'a, T> = std::result::Result<(T, &'a [Tok]), String>;
+
+fn first(tok: &[Tok]) -> std::result::Result<&Tok, String> {
+    tok.first()
+        .ok_or_else(|| "unexpected end of the pattern".to_string())
+}
+
+fn parse_pattern(tok: &[Tok]) -> ParseResult<'_, Pat> {
+    let (mut pat, mut tok) = parse_pattern_nosubst(tok)?;
+    while let Some(Tok::LBracket) = tok.first() {
+        tok = &tok[1..];
+
+        // `?f[]`
+        if let Tok::RBracket = first(tok)? {
+            pat = ho_var(pat, Vec::new())?;
+            tok = &tok[1..];
+            continue;
+        }
+
+        let (arg, tok2) = parse_elem(tok)?;
+        tok = tok2;
+        let l = match (arg, first(tok)?) {
+            (Elem::Pat(l), Tok::ColonEquals) => l,
+
+            // `?f[a, ...]`
+            (arg, _) => {
+                let mut args = vec![arg];
+                while let Tok::Comma = first(tok)? {
+                    let (arg, tok2) = parse_elem(&tok[1..])?;
+                    args.push(arg);
+                    tok = tok2;
+                }
+                let Tok::RBracket = first(tok)? else {
+                    return Err(format!("expected `]` at {tok:?}"));
+                };
+                tok = &tok[1..];
+                pat = ho_var(pat, args)?;
+                continue;
+            }
+        };
+        let (r, tok2) = parse_pattern(&tok[1..])?;
+        tok = tok2;
+        let mut sigma = vec![(l, r)];
+
+        // `b[x := t, y := u, ...]`
+        while let Tok::Comma = first(tok)? {
+            let (l, tok2) = parse_pattern(&tok[1..])?;
+            let Tok::ColonEquals = first(tok2)? else {
+                return Err(format!("expected `:=` at {tok2:?}"));
+            };
+            let (r, tok2) = parse_pattern(&tok2[1..])?;
+            tok = tok2;
+            sigma.push((l, r));
+        }
+        let Tok::RBracket = first(tok)? else {
+            return Err(format!("expected `]` at {tok:?}"));
+        };
+        tok = &tok[1..];
+        pat = Pat::Subst(Box::new(pat), sigma);
+    }
+    Ok((pat, tok))
+}
+
+// only pattern variables can have arguments.
+fn ho_var(pat: Pat, args: Vec<Elem>) -> std::result::Result<Pat, String> {
+    let Pat::PVar(v) = pat else {
+        return Err("only pattern variables can have arguments `[..]`".to_string());
+    };
+    Ok(Pat::HOVar(v, args))
+}
+
+fn parse_elem(tok: &[Tok]) -> ParseResult<'_, Elem> {
+    if let Tok::Slot(s) = first(tok)? {
+        return Ok((Elem::Slot(s.clone()), &tok[1..]));
+    }
+    parse_pattern(tok).map(|(x, rest)| (Elem::Pat(x), rest))
+}
+
+fn parse_pattern_nosubst(mut tok: &[Tok]) -> ParseResult<'_, Pat> {
+    match first(tok)? {
+        Tok::PVar(v) => Ok((Pat::PVar(v.clone()), &tok[1..])),
+        Tok::Ident(x) if x == "_" => Ok((Pat::Wildcard, &tok[1..])),
+        Tok::Ident(x) => Ok((Pat::Const(x.clone()), &tok[1..])),
+        Tok::LParen => {
+            tok = &tok[1..];
+
+            // `(| p1 p2)` and `(?op p1 p2)`
+            let special = match first(tok)? {
+                Tok::Ident(op) if op == "|" => Some(None),
+                Tok::PVar(op) => Some(Some(op.clone())),
+                _ => None,
+            };
+            if let Some(op) = special {
+                tok = &tok[1..];
+                let mut children = Vec::new();
+                while !matches!(first(tok)?, Tok::RParen) {
+                    let (child, tok2) = parse_pattern(tok)?;
+                    children.push(child);
+                    tok = tok2;
+                }
+                let pat = match op {
+                    None => Pat::Or(children),
+                    Some(op) => Pat::OpVar(op, children),
+                };
+                return Ok((pat, &tok[1..]));
+            }
+
+            let Tok::Ident(op) = first(tok)? else {
+                return Err(format!("expected an operator at {tok:?}"));
+            };
+            tok = &tok[1..];
+            let mut elems = Vec::new();
+            while !matches!(first(tok)?, Tok::RParen) {
+                let (elem, tok2) = parse_elem(tok)?;
+                elems.push(elem);
+                tok = tok2;
+            }
+            Ok((Pat::ENode(op.clone(), elems), &tok[1..]))
+        }
+        t => Err(format!("unexpected token {t:?}")),
+    }
+}
+
+impl Pat {
+    fn to_tokens(&self, lang: &Type, span: Span) -> TokenStream2 {
+        let rec = |p: &Pat| p.to_tokens(lang, span);
+        let elem = |e: &Elem| match e {
+            Elem::Slot(s) => {
+                quote! { ::slotted_egraphs::HOArg::Slot(::slotted_egraphs::Slot::named(#s)) }
+            }
+            Elem::Pat(p) => {
+                let p = rec(p);
+                quote! { ::slotted_egraphs::HOArg::Term(#p) }
+            }
+        };
+        match self {
+            Pat::ENode(op, elems) => {
+                // the compiler checks that the operator exists and gets the right number of slots and children.
+                let f = pattern_op_ident(op, span);
+                let args = elems.iter().map(|e| match e {
+                    Elem::Slot(s) => quote! { ::slotted_egraphs::Slot::named(#s) },
+                    Elem::Pat(_) => quote! { ::slotted_egraphs::AppliedId::null() },
+                });
+                let children = elems.iter().filter_map(|e| match e {
+                    Elem::Slot(_) => None,
+                    Elem::Pat(p) => Some(rec(p)),
+                });
+                let node = quote_spanned! {span=> <#lang>::#f(#(#args),*) };
+                quote! { ::slotted_egraphs::Pattern::ENode(#node, vec![#(#children),*]) }
+            }
+            Pat::Const(x) => {
+                let msg = format!("`{x}` is not a constant of the language");
+                // the assertion is evaluated at compile time, so `from_syntax` can only fail for literals.
+                quote_spanned! {span=>
+                    ::slotted_egraphs::Pattern::ENode(
+                        {
+                            const _: () = assert!(<#lang>::__pattern_const(#x), #msg);
+                            <#lang as ::slotted_egraphs::Language>::from_syntax(&[::slotted_egraphs::SyntaxElem::String(String::from(#x))]).expect(#msg)
+                        },
+                        Vec::new(),
+                    )
+                }
+            }
+            Pat::PVar(v) => quote! { ::slotted_egraphs::Pattern::PVar(String::from(#v)) },
+            Pat::Subst(b, sigma) => {
+                let b = rec(b);
+                let sigma = sigma.iter().map(|(x, t)| {
+                    let (x, t) = (rec(x), rec(t));
+                    quote! { (#x, #t) }
+                });
+                quote! { ::slotted_egraphs::Pattern::Subst(Box::new(#b), vec![#(#sigma),*]) }
+            }
+            Pat::HOVar(f, args) => {
+                let args = args.iter().map(elem);
+                quote! { ::slotted_egraphs::Pattern::HOVar(String::from(#f), vec![#(#args),*]) }
+            }
+            Pat::Wildcard => quote! { ::slotted_egraphs::Pattern::Wildcard },
+            Pat::Or(alts) => {
+                let alts = alts.iter().map(rec);
+                quote! { ::slotted_egraphs::Pattern::Or(vec![#(#alts),*]) }
+            }
+            Pat::OpVar(op, children) => {
+                let children = children.iter().map(rec);
+                quote! { ::slotted_egraphs::Pattern::OpVar(String::from(#op), vec![#(#children),*]) }
+            }
+        }
+    }
+}
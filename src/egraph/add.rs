@@ -190,6 +190,13 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             let usages = &mut self.classes.get_mut(&ref_id).unwrap().usages;
             usages.insert(sh.clone());
         }
+        *self
+            .op_index
+            .entry(operator_key(&sh))
+            .or_default()
+            .entry(id)
+            .or_default() += 1;
+        self.mark_changed(id);
     }
 
     pub(in crate::egraph) fn raw_remove_from_class(&mut self, id: Id, sh: L) -> ProvenSourceNode {
@@ -204,6 +211,17 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             usages.remove(&sh);
         }
 
+        let key = operator_key(&sh);
+        let classes = self.op_index.get_mut(&key).unwrap();
+        let count = classes.get_mut(&id).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            classes.remove(&id);
+            if classes.is_empty() {
+                self.op_index.remove(&key);
+            }
+        }
+
         opt_psn.unwrap()
     }
 }
@@ -233,6 +251,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             usages: HashSet::default(),
             syn_enode: syn_enode.clone(),
             analysis_data: N::make(&self, &syn_enode),
+            changed_at: self.timestamp(),
         };
         self.classes.insert(c_id, c);
 
@@ -20,6 +20,7 @@ pub use analysis::*;
 use vec_collections::AbstractVecSet;
 
 use std::cell::RefCell;
+use std::time::Duration;
 
 // invariants:
 // 1. If two ENodes (that are in the EGraph) have equal .shape(), they have to be in the same eclass.
@@ -49,6 +50,10 @@ pub struct EGraph<L: Language, N: Analysis<L> = ()> {
     // TODO remove this if explanations are disabled.
     syn_hashcons: HashMap<L, AppliedId>,
 
+    // Maps each operator (see `operator_key`) to the classes containing e-nodes with that operator,
+    // together with the number of such e-nodes in the class.
+    op_index: HashMap<L, HashMap<Id, usize>>,
+
     // E-Nodes that need to be re-processed, stored as shapes.
     pending: HashMap<L, PendingType>,
 
@@ -57,11 +62,27 @@ pub struct EGraph<L: Language, N: Analysis<L> = ()> {
 
     pub(crate) subst_method: Option<Box<dyn SubstMethod<L, N>>>,
 
+    // The constructor of variable e-nodes like `(var $x)`, see `set_var_node`.
+    pub(crate) var_node: Option<fn(Slot) -> L>,
+
     pub analysis: N,
 
     // N::modify(_) will be run on these classes.
     // We delay handling modify so that all invariants can be rebuild again, first.
     modify_queue: Vec<Id>,
+
+    // Incremented on every change of an e-class, see `EClass::changed_at`.
+    clock: usize,
+
+    // The number of calls to `union_instantiations` that changed the e-graph.
+    // `union` and `union_justified` are implemented by it, so all unions of rules are counted, but not the ones of rebuilding.
+    union_count: usize,
+
+    // The total time spent in `rebuild`.
+    pub(crate) rebuild_time: Duration,
+
+    // The largest size of `pending` during `rebuild`, since it was last reset by the Runner.
+    pub(crate) max_pending: usize,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -93,6 +114,10 @@ pub(crate) struct EClass<L: Language, N: Analysis<L>> {
     syn_enode: L,
 
     analysis_data: N::Data,
+
+    // The value of `EGraph::timestamp` at the last time this class gained e-nodes, or changed its slots or symmetries.
+    // This is used for incremental e-matching.
+    changed_at: usize,
 }
 
 impl<L: Language, N: Analysis<L> + Default> Default for EGraph<L, N> {
@@ -114,14 +139,33 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             classes: Default::default(),
             hashcons: Default::default(),
             syn_hashcons: Default::default(),
+            op_index: Default::default(),
             pending: Default::default(),
             proof_registry: ProofRegistry::default(),
             subst_method: Some(S::new_boxed()),
+            var_node: None,
             analysis,
             modify_queue: Vec::new(),
+            clock: 0,
+            union_count: 0,
+            rebuild_time: Duration::ZERO,
+            max_pending: 0,
         }
     }
 
+    /// Declares the constructor of the variable e-nodes of the language, e.g. `Lambda::Var`.
+    ///
+    /// Substitutions of variables for variables `b[(var $x) := (var $y)]` are then done by renaming the slots of `b`,
+    /// instead of running the [SubstMethod].
+    pub fn set_var_node(&mut self, var: fn(Slot) -> L) {
+        self.var_node = Some(var);
+    }
+
+    /// The hit statistics of the [SubstMethod], if it caches its results (see [MemoSubst]).
+    pub fn subst_cache_stats(&self) -> Option<SubstCacheStats> {
+        self.subst_method.as_ref()?.cache_stats()
+    }
+
     pub fn slots(&self, id: Id) -> SmallHashSet<Slot> {
         self.classes[&id].slots.clone()
     }
@@ -194,6 +238,46 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         result
     }
 
+    /// Returns the e-classes containing an e-node with the same operator as `n`, in ascending order.
+    ///
+    /// Two e-nodes have the same operator, if they only differ in their slots and children.
+    pub fn classes_with_operator(&self, n: &L) -> Vec<Id> {
+        let Some(classes) = self.op_index.get(&operator_key(n)) else {
+            return Vec::new();
+        };
+        let mut out: Vec<Id> = classes.keys().copied().collect();
+        out.sort();
+        out
+    }
+
+    /// Returns the current time of the e-graph, which increases with each change of an e-class.
+    ///
+    /// Use it together with [EGraph::changed_since].
+    pub fn timestamp(&self) -> usize {
+        self.clock
+    }
+
+    /// Returns the (alive) e-classes that gained e-nodes, slots or symmetries, or that were merged with another class after the time `t`.
+    pub fn changed_since(&self, t: usize) -> Vec<Id> {
+        self.ids()
+            .into_iter()
+            .filter(|i| self.classes[i].changed_at > t)
+            .collect()
+    }
+
+    // marks the class `i` as changed.
+    pub(crate) fn mark_changed(&mut self, i: Id) {
+        self.clock += 1;
+        self.classes.get_mut(&i).unwrap().changed_at = self.clock;
+    }
+
+    /// The number of unions (by [EGraph::union], [EGraph::union_justified] or [EGraph::union_instantiations]) that changed the e-graph so far.
+    ///
+    /// Unions that are implied by congruence, and made while rebuilding, are not counted.
+    pub fn union_count(&self) -> usize {
+        self.union_count
+    }
+
     // number of enodes in the egraph.
     pub fn total_number_of_nodes(&self) -> usize {
         self.hashcons.len()
@@ -286,6 +370,15 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         println!("");
     }
 
+    // the e-classes containing e-nodes that refer to `i`.
+    pub(crate) fn parent_classes(&self, i: Id) -> HashSet<Id> {
+        self.classes[&i]
+            .usages
+            .iter()
+            .map(|x| self.hashcons[x])
+            .collect()
+    }
+
     // The resulting e-nodes are written as they exist in the e-class.
     pub(crate) fn usages(&self, i: Id) -> Vec<L> {
         let mut out = Vec::new();
@@ -1,4 +1,5 @@
 use crate::*;
+use std::time::Instant;
 
 impl<L: Language, N: Analysis<L>> EGraph<L, N> {
     // proof.l should be i.
@@ -137,7 +138,9 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             self.check();
         }
 
+        let start = Instant::now();
         while let Some(sh) = self.pending.keys().cloned().next() {
+            self.max_pending = self.max_pending.max(self.pending.len());
             let pending_ty = self.pending.remove(&sh).unwrap();
             self.handle_pending(sh, pending_ty);
 
@@ -150,6 +153,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             let i = self.find_id(i);
             N::modify(self, i);
         }
+        self.rebuild_time += start.elapsed();
     }
 
     fn handle_pending(&mut self, sh: L, pending_ty: PendingType) {
@@ -301,6 +305,9 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             let v = self.pending.entry(sh.clone()).or_insert(pending_ty);
             *v = v.merge(pending_ty);
         }
+        if let PendingType::Full = pending_ty {
+            self.mark_changed(i);
+        }
     }
 
     pub(crate) fn pc_from_shape(&self, sh: &L) -> ProvenContains<L> {
@@ -25,8 +25,8 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         subst: &Subst,
         #[allow(unused)] justification: Option<String>,
     ) -> bool {
-        let a = pattern_subst(self, from_pat, subst);
-        let b = pattern_subst(self, to_pat, subst);
+        let a = instantiate_lhs(self, from_pat, subst);
+        let b = pattern_subst_with_params(self, to_pat, subst, &ho_params(from_pat));
 
         #[allow(unused)]
         let syn_a = self.synify_app_id(a.clone());
@@ -37,6 +37,9 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
 
         let out = self.union_internal(&a, &b, proof);
         self.rebuild_called_from_union_instantiations();
+        if out {
+            self.union_count += 1;
+        }
         out
     }
 
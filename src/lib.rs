@@ -35,7 +35,7 @@ const CHECKS: bool = true;
 #[cfg(not(feature = "checks"))]
 const CHECKS: bool = false;
 
-pub use slotted_egraphs_derive::define_language;
+pub use slotted_egraphs_derive::{define_language, pattern};
 
 mod slot;
 pub use slot::*;
@@ -45,6 +45,7 @@ pub use types::*;
 
 mod parse;
 pub(crate) use parse::*;
+pub use parse::{ParseError, Token};
 
 mod lang;
 pub use lang::*;
@@ -71,3 +72,6 @@ use group::*;
 
 mod run;
 pub use run::*;
+
+mod synth;
+pub use synth::*;
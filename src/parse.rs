@@ -8,6 +8,13 @@ pub enum ParseError {
     FromSyntaxFailed(Vec<SyntaxElem>),
     ExpectedColonEquals(Vec<Token>),
     ExpectedRBracket(Vec<Token>),
+    UnexpectedEnd,
+    ExpectedEquation(String),
+    ExpectedPVar(String),
+    SubstInMultiPattern(String),
+    UnsupportedInMultiPattern(String),
+    ExpectedCondition(Vec<Token>),
+    ArgsWithoutPVar(Vec<Token>), // `t[a, ...]`, where `t` isn't a pattern variable.
 }
 
 #[derive(Debug, Clone)]
@@ -16,6 +23,7 @@ pub enum Token {
     Ident(String), // map, 15
     PVar(String),  // ?x
     ColonEquals,   // :=
+    Comma,         // ,
     LParen,        // (
     RParen,        // )
     LBracket,      // [
@@ -26,7 +34,7 @@ fn ident_char(c: char) -> bool {
     if c.is_whitespace() {
         return false;
     }
-    if "()[]".contains(c) {
+    if "()[],".contains(c) {
         return false;
     }
     true
@@ -46,7 +54,7 @@ fn crop_ident(s: &str) -> Result<(/*ident*/ &str, /*rest*/ &str), ParseError> {
     Ok(out)
 }
 
-fn tokenize(mut s: &str) -> Result<Vec<Token>, ParseError> {
+pub(crate) fn tokenize(mut s: &str) -> Result<Vec<Token>, ParseError> {
     let mut tokens = Vec::new();
 
     loop {
@@ -67,6 +75,9 @@ fn tokenize(mut s: &str) -> Result<Vec<Token>, ParseError> {
         } else if s.starts_with(']') {
             tokens.push(Token::RBracket);
             s = &s[1..];
+        } else if s.starts_with(',') {
+            tokens.push(Token::Comma);
+            s = &s[1..];
         } else if s.starts_with(":=") {
             tokens.push(Token::ColonEquals);
             s = &s[2..];
@@ -112,71 +123,198 @@ impl<L: Language> RecExpr<L> {
 impl<L: Language> MultiPattern<L> {
     // "?a == pat, ?b == pat, ..."
     pub fn parse(s: &str) -> Result<Self, ParseError> {
-        let mut out = Vec::new();
-        for x in s.split(",") {
+        let mut eqs = Vec::new();
+        for x in split_top_level(s) {
             let x = x.trim();
-            if x.is_empty() { continue }
+            if x.is_empty() {
+                continue;
+            }
 
             let v: Box<[&str]> = x.split("==").collect();
-            assert_eq!(v.len(), 2);
-            let var: Pattern<L> = Pattern::parse(v[0])?;
-            let rhs: Pattern<L> = Pattern::parse(v[1])?;
-            let Pattern::PVar(v) = var else { panic!("{var} isn't a PVar") };
-            let Pattern::ENode(n, children) = rhs else { panic!("{rhs} isn't an e-node") };
-            let children = children.into_iter().map(|x| {
-                let Pattern::PVar(xx) = x else { panic!("child {x} isn't a PVar") };
-                xx
-            }).collect();
-            out.push((v, n, children));
+            let [l, r] = &*v else {
+                return Err(ParseError::ExpectedEquation(x.to_string()));
+            };
+            let l: Pattern<L> = Pattern::parse(l)?;
+            let r: Pattern<L> = Pattern::parse(r)?;
+            let eq = match (l, r) {
+                (Pattern::PVar(v), pat) | (pat, Pattern::PVar(v)) => (v, pat),
+                (l, _) => return Err(ParseError::ExpectedPVar(l.to_string())),
+            };
+            eqs.push(eq);
         }
-        Ok(MultiPattern { pats: out })
+        MultiPattern::from_equations(eqs)
     }
 }
 
-fn parse_pattern<L: Language>(tok: &[Token]) -> Result<(Pattern<L>, &[Token]), ParseError> {
+pub(crate) fn parse_pattern<L: Language>(
+    tok: &[Token],
+) -> Result<(Pattern<L>, &[Token]), ParseError> {
     let (mut pat, mut tok) = parse_pattern_nosubst(tok)?;
     while let Some(Token::LBracket) = tok.get(0) {
+        let bracket = tok;
         tok = &tok[1..];
-        let (l, tok2) = parse_pattern(tok)?;
+
+        // `?f[]`
+        if let Token::RBracket = first(tok)? {
+            pat = ho_var(pat, Vec::new(), bracket)?;
+            tok = &tok[1..];
+            continue;
+        }
+
+        let (arg, tok2) = parse_ho_arg(tok)?;
         tok = tok2;
 
-        let Token::ColonEquals = &tok[0] else {
-            return Err(ParseError::ExpectedColonEquals(to_vec(tok)));
+        let l = match (arg, first(tok)?) {
+            (HOArg::Term(l), Token::ColonEquals) => l,
+
+            // `?f[a, ...]`
+            (arg, _) => {
+                let mut args = vec![arg];
+                while let Token::Comma = first(tok)? {
+                    let (arg, tok2) = parse_ho_arg(&tok[1..])?;
+                    args.push(arg);
+                    tok = tok2;
+                }
+                let Token::RBracket = first(tok)? else {
+                    return Err(ParseError::ExpectedRBracket(to_vec(tok)));
+                };
+                tok = &tok[1..];
+                pat = ho_var(pat, args, bracket)?;
+                continue;
+            }
         };
         tok = &tok[1..];
 
         let (r, tok2) = parse_pattern(tok)?;
         tok = tok2;
+        let mut sigma = vec![(l, r)];
+
+        // `b[x := t, y := u, ...]`
+        while let Token::Comma = first(tok)? {
+            let (l, tok2) = parse_pattern(&tok[1..])?;
+            tok = tok2;
+            let Token::ColonEquals = first(tok)? else {
+                return Err(ParseError::ExpectedColonEquals(to_vec(tok)));
+            };
+            let (r, tok2) = parse_pattern(&tok[1..])?;
+            tok = tok2;
+            sigma.push((l, r));
+        }
 
-        let Token::RBracket = &tok[0] else {
+        let Token::RBracket = first(tok)? else {
             return Err(ParseError::ExpectedRBracket(to_vec(tok)));
         };
         tok = &tok[1..];
 
-        pat = Pattern::Subst(Box::new(pat), Box::new(l), Box::new(r));
+        pat = Pattern::Subst(Box::new(pat), sigma);
     }
     Ok((pat, tok))
 }
 
+fn parse_ho_arg<L: Language>(tok: &[Token]) -> Result<(HOArg<L>, &[Token]), ParseError> {
+    if let Token::Slot(slot) = first(tok)? {
+        return Ok((HOArg::Slot(*slot), &tok[1..]));
+    }
+    parse_pattern(tok).map(|(x, rest)| (HOArg::Term(x), rest))
+}
+
+// only pattern variables can have arguments.
+fn ho_var<L: Language>(
+    pat: Pattern<L>,
+    args: Vec<HOArg<L>>,
+    tok: &[Token],
+) -> Result<Pattern<L>, ParseError> {
+    let Pattern::PVar(v) = pat else {
+        return Err(ParseError::ArgsWithoutPVar(to_vec(tok)));
+    };
+    Ok(Pattern::HOVar(v, args))
+}
+
+impl<L: Language> MultiApplier<L> {
+    // "?a == pat, ?b == pat, ..."
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut eqs = Vec::new();
+        for x in split_top_level(s) {
+            let x = x.trim();
+            if x.is_empty() {
+                continue;
+            }
+
+            let Some((v, rhs)) = x.split_once("==") else {
+                return Err(ParseError::ExpectedEquation(x.to_string()));
+            };
+            let Pattern::PVar(v) = Pattern::<L>::parse(v)? else {
+                return Err(ParseError::ExpectedPVar(v.trim().to_string()));
+            };
+            eqs.push((v, Pattern::parse(rhs)?));
+        }
+        if eqs.is_empty() {
+            return Err(ParseError::ExpectedEquation(s.to_string()));
+        }
+        Ok(MultiApplier { eqs })
+    }
+}
+
+// splits at the commas, that are not nested within parentheses or brackets.
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                out.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&s[start..]);
+    out
+}
+
 fn parse_pattern_nosubst<L: Language>(
     mut tok: &[Token],
 ) -> Result<(Pattern<L>, &[Token]), ParseError> {
-    if let Token::PVar(p) = &tok[0] {
+    if let Token::PVar(p) = first(tok)? {
         let pat = Pattern::PVar(p.to_string());
         return Ok((pat, &tok[1..]));
     }
 
-    if let Token::LParen = tok[0] {
+    if let Token::LParen = first(tok)? {
         tok = &tok[1..];
 
-        let Token::Ident(op) = &tok[0] else {
+        // `(| p1 p2)` and `(?op p1 p2)`
+        let special = match first(tok)? {
+            Token::Ident(op) if op == "|" => Some(None),
+            Token::PVar(op) => Some(Some(op.to_string())),
+            _ => None,
+        };
+        if let Some(op) = special {
+            tok = &tok[1..];
+            let mut children = Vec::new();
+            while !matches!(first(tok)?, Token::RParen) {
+                let (child, tok2) = parse_pattern(tok)?;
+                children.push(child);
+                tok = tok2;
+            }
+            let pat = match op {
+                None => Pattern::Or(children),
+                Some(op) => Pattern::OpVar(op, children),
+            };
+            return Ok((pat, &tok[1..]));
+        }
+
+        let Token::Ident(op) = first(tok)? else {
             return Err(ParseError::ParseState(to_vec(tok)));
         };
         tok = &tok[1..];
 
         let mut syntax_elems = vec![NestedSyntaxElem::String(op.to_string())];
         loop {
-            if let Token::RParen = tok[0] {
+            if let Token::RParen = first(tok)? {
                 break;
             };
 
@@ -207,11 +345,15 @@ fn parse_pattern_nosubst<L: Language>(
         let re = Pattern::ENode(node, syntax_elems);
         Ok((re, tok))
     } else {
-        let Token::Ident(op) = &tok[0] else {
+        let Token::Ident(op) = first(tok)? else {
             return Err(ParseError::ParseState(to_vec(tok)));
         };
         tok = &tok[1..];
 
+        if op == "_" {
+            return Ok((Pattern::Wildcard, tok));
+        }
+
         let elems = [SyntaxElem::String(op.to_string())];
         let node =
             L::from_syntax(&elems).ok_or_else(|| ParseError::FromSyntaxFailed(to_vec(&elems)))?;
@@ -230,13 +372,118 @@ enum NestedSyntaxElem<L: Language> {
 fn parse_nested_syntax_elem<L: Language>(
     tok: &[Token],
 ) -> Result<(NestedSyntaxElem<L>, &[Token]), ParseError> {
-    if let Token::Slot(slot) = &tok[0] {
+    if let Token::Slot(slot) = first(tok)? {
         return Ok((NestedSyntaxElem::Slot(*slot), &tok[1..]));
     }
 
     parse_pattern::<L>(tok).map(|(x, rest)| (NestedSyntaxElem::Pattern(x), rest))
 }
 
+impl Condition {
+    /// Parses a list of conditions `c1, c2, ...`, which all have to hold. See [Condition] for the syntax.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let tok = tokenize(s)?;
+        let (c, rest) = parse_conditions(&tok)?;
+
+        if !rest.is_empty() {
+            return Err(ParseError::RemainingRest(to_vec(rest)));
+        }
+
+        Ok(c)
+    }
+}
+
+// parses the rhs of a rule, optionally followed by `where c1, c2, ...`.
+pub(crate) fn parse_rhs<L: Language>(
+    s: &str,
+) -> Result<(Pattern<L>, Option<Condition>), ParseError> {
+    let tok = tokenize(s)?;
+    let (pat, tok) = parse_pattern(&tok)?;
+
+    let (cond, rest) = match tok {
+        [] => (None, tok),
+        [Token::Ident(w), rest @ ..] if w == "where" => {
+            let (c, rest) = parse_conditions(rest)?;
+            (Some(c), rest)
+        }
+        _ => (None, tok),
+    };
+
+    if !rest.is_empty() {
+        return Err(ParseError::RemainingRest(to_vec(rest)));
+    }
+
+    Ok((pat, cond))
+}
+
+// parses `c1, c2, ...` into their conjunction. The commas are optional.
+pub(crate) fn parse_conditions(mut tok: &[Token]) -> Result<(Condition, &[Token]), ParseError> {
+    let mut cs = Vec::new();
+    loop {
+        let (c, tok2) = parse_condition(tok)?;
+        cs.push(c);
+        tok = tok2;
+
+        match tok.first() {
+            Some(Token::Comma) => tok = &tok[1..],
+            Some(Token::Slot(_) | Token::LParen) => {}
+            _ => break,
+        }
+    }
+
+    let c = if cs.len() == 1 {
+        cs.pop().unwrap()
+    } else {
+        Condition::And(cs)
+    };
+    Ok((c, tok))
+}
+
+fn parse_condition(tok: &[Token]) -> Result<(Condition, &[Token]), ParseError> {
+    let err = || ParseError::ExpectedCondition(to_vec(tok));
+
+    match tok {
+        [Token::Slot(s), Token::Ident(hash), Token::PVar(v), rest @ ..] if hash == "#" => {
+            Ok((Condition::Fresh(*s, v.clone()), rest))
+        }
+        [Token::LParen, Token::Ident(op), rest @ ..] => {
+            let (c, rest) = match (&**op, rest) {
+                ("slot_free_in", [Token::Slot(s), Token::PVar(v), rest @ ..]) => {
+                    (Condition::Fresh(*s, v.clone()), rest)
+                }
+                ("not", rest) => {
+                    let (c, rest) = parse_condition(rest)?;
+                    (Condition::Not(Box::new(c)), rest)
+                }
+                ("and" | "or", mut rest) => {
+                    let mut cs = Vec::new();
+                    while !matches!(first(rest)?, Token::RParen) {
+                        let (c, rest2) = parse_condition(rest)?;
+                        cs.push(c);
+                        rest = rest2;
+                    }
+                    if cs.is_empty() {
+                        return Err(err());
+                    }
+                    let c = if op == "and" {
+                        Condition::And(cs)
+                    } else {
+                        Condition::Or(cs)
+                    };
+                    (c, rest)
+                }
+                _ => return Err(err()),
+            };
+
+            let [Token::RParen, rest @ ..] = rest else {
+                return Err(err());
+            };
+            Ok((c, rest))
+        }
+        _ => Err(err()),
+    }
+}
+
 // print:
 impl<L: Language> std::fmt::Display for Pattern<L> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -272,7 +519,68 @@ impl<L: Language> std::fmt::Display for Pattern<L> {
                 Ok(())
             }
             Pattern::PVar(p) => write!(f, "?{p}"),
-            Pattern::Subst(b, x, t) => write!(f, "{b}[{x} := {t}]"),
+            Pattern::Subst(b, sigma) => {
+                write!(f, "{b}[")?;
+                for (i, (x, t)) in sigma.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{x} := {t}")?;
+                }
+                write!(f, "]")
+            }
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Or(children) => {
+                write!(f, "(|")?;
+                for c in children {
+                    write!(f, " {c}")?;
+                }
+                write!(f, ")")
+            }
+            Pattern::OpVar(op, children) => {
+                write!(f, "(?{op}")?;
+                for c in children {
+                    write!(f, " {c}")?;
+                }
+                write!(f, ")")
+            }
+            Pattern::HOVar(v, args) => {
+                write!(f, "?{v}[")?;
+                for (i, a) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{a}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |f: &mut std::fmt::Formatter<'_>, op: &str, cs: &[Condition]| {
+            write!(f, "({op}")?;
+            for c in cs {
+                write!(f, " {c}")?;
+            }
+            write!(f, ")")
+        };
+        match self {
+            Condition::Fresh(s, v) => write!(f, "{s} # ?{v}"),
+            Condition::Not(c) => write!(f, "(not {c})"),
+            Condition::And(cs) => list(f, "and", cs),
+            Condition::Or(cs) => list(f, "or", cs),
+        }
+    }
+}
+
+impl<L: Language> std::fmt::Display for HOArg<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HOArg::Slot(s) => write!(f, "{s}"),
+            HOArg::Term(t) => write!(f, "{t}"),
         }
     }
 }
@@ -295,14 +603,32 @@ impl<L: Language> std::fmt::Debug for RecExpr<L> {
     }
 }
 
-
 impl<L: Language> std::fmt::Display for MultiPattern<L> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for (i, (pv, n, children)) in self.pats.iter().enumerate() {
+        let mut eqs = Vec::new();
+        for (pv, n, children) in &self.pats {
             let children = children.iter().map(|x| Pattern::PVar(x.clone())).collect();
             let pat = Pattern::ENode(n.clone(), children);
-            write!(f, "{pv} == {pat}")?;
-            if i != self.pats.len()-1 {
+            eqs.push(format!("?{pv} == {pat}"));
+        }
+        for (x, y) in &self.aliases {
+            eqs.push(format!("?{x} == ?{y}"));
+        }
+        write!(f, "{}", eqs.join(", "))
+    }
+}
+
+impl<L: Language> std::fmt::Debug for MultiPattern<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl<L: Language> std::fmt::Display for MultiApplier<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (pv, pat)) in self.eqs.iter().enumerate() {
+            write!(f, "?{pv} == {pat}")?;
+            if i != self.eqs.len() - 1 {
                 write!(f, ", ")?;
             }
         }
@@ -310,12 +636,15 @@ impl<L: Language> std::fmt::Display for MultiPattern<L> {
     }
 }
 
-impl<L: Language> std::fmt::Debug for MultiPattern<L> {
+impl<L: Language> std::fmt::Debug for MultiApplier<L> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self)
     }
 }
 
+fn first(tok: &[Token]) -> Result<&Token, ParseError> {
+    tok.first().ok_or(ParseError::UnexpectedEnd)
+}
 
 fn to_vec<T: Clone>(t: &[T]) -> Vec<T> {
     t.iter().cloned().collect()
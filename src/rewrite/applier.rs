@@ -0,0 +1,125 @@
+use crate::*;
+
+/// The right-hand side of a [Rewrite]: applies a rule to the matches found by a [Searcher].
+pub trait Applier<L: Language, N: Analysis<L>> {
+    /// Applies the rule to a single match.
+    ///
+    /// `lhs` is the pattern of the [Searcher] (see [Searcher::get_pattern]), and `rule` is the name of the [Rewrite].
+    /// Returns whether the e-graph changed.
+    fn apply_one(
+        &self,
+        eg: &mut EGraph<L, N>,
+        subst: &Subst,
+        lhs: Option<&Pattern<L>>,
+        rule: &str,
+    ) -> bool;
+
+    /// Checks whether this applier can be applied to the matches of a [Searcher] with the pattern `lhs`,
+    /// see [Rewrite::try_from_parts].
+    fn validate(&self, _lhs: Option<&Pattern<L>>) -> Result<(), RewriteError> {
+        Ok(())
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Applier<L, N> for Pattern<L> {
+    fn apply_one(
+        &self,
+        eg: &mut EGraph<L, N>,
+        subst: &Subst,
+        lhs: Option<&Pattern<L>>,
+        rule: &str,
+    ) -> bool {
+        let lhs = expect_lhs(lhs, rule);
+        eg.union_instantiations(lhs, self, subst, Some(rule.to_string()))
+    }
+
+    fn validate(&self, lhs: Option<&Pattern<L>>) -> Result<(), RewriteError> {
+        validate_rule(lhs.ok_or(RewriteError::MissingLhs)?, self, None)
+    }
+}
+
+/// Closures can be used as an [Applier].
+///
+/// They compute the e-class that the matched term should be equal to, or `None` if nothing should be done.
+impl<L, N, F> Applier<L, N> for F
+where
+    L: Language,
+    N: Analysis<L>,
+    F: Fn(&Subst, &mut EGraph<L, N>) -> Option<AppliedId>,
+{
+    fn apply_one(
+        &self,
+        eg: &mut EGraph<L, N>,
+        subst: &Subst,
+        lhs: Option<&Pattern<L>>,
+        rule: &str,
+    ) -> bool {
+        let Some(new) = self(subst, eg) else {
+            return false;
+        };
+        let orig = instantiate_lhs(eg, expect_lhs(lhs, rule), subst);
+        eg.union_justified(&orig, &new, Some(rule.to_string()))
+    }
+
+    fn validate(&self, lhs: Option<&Pattern<L>>) -> Result<(), RewriteError> {
+        lhs.map(|_| ()).ok_or(RewriteError::MissingLhs)
+    }
+}
+
+/// An [Applier] that only applies `applier`, if the condition `cond` holds.
+pub struct ConditionalApplier<C, A> {
+    pub cond: C,
+    pub applier: A,
+}
+
+impl<L: Language, N: Analysis<L>, C: Cond<L, N>, A: Applier<L, N>> Applier<L, N>
+    for ConditionalApplier<C, A>
+{
+    fn apply_one(
+        &self,
+        eg: &mut EGraph<L, N>,
+        subst: &Subst,
+        lhs: Option<&Pattern<L>>,
+        rule: &str,
+    ) -> bool {
+        if self.cond.check(subst, eg) {
+            self.applier.apply_one(eg, subst, lhs, rule)
+        } else {
+            false
+        }
+    }
+
+    fn validate(&self, lhs: Option<&Pattern<L>>) -> Result<(), RewriteError> {
+        self.applier.validate(lhs)
+    }
+}
+
+/// The right-hand side of a multi-pattern [Rewrite]: equations `?a == pat`, whose sides are unioned.
+///
+/// See [Rewrite::new_multi].
+pub struct MultiApplier<L: Language> {
+    pub(crate) eqs: Vec<(PVar, Pattern<L>)>,
+}
+
+impl<L: Language, N: Analysis<L>> Applier<L, N> for MultiApplier<L> {
+    fn apply_one(
+        &self,
+        eg: &mut EGraph<L, N>,
+        subst: &Subst,
+        _lhs: Option<&Pattern<L>>,
+        rule: &str,
+    ) -> bool {
+        let mut changed = false;
+        for (v, pat) in &self.eqs {
+            let v = Pattern::PVar(v.clone());
+            changed |= eg.union_instantiations(&v, pat, subst, Some(rule.to_string()));
+        }
+        changed
+    }
+}
+
+fn expect_lhs<'a, L: Language>(lhs: Option<&'a Pattern<L>>, rule: &str) -> &'a Pattern<L> {
+    lhs.unwrap_or_else(|| {
+        panic!("rule `{rule}`: this applier requires a searcher with a single pattern")
+    })
+}
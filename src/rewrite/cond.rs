@@ -0,0 +1,182 @@
+use crate::*;
+use std::fmt::{self, Display, Formatter};
+
+/// A condition of a conditional [Rewrite], see [Rewrite::new_cond].
+///
+/// Closures `Fn(&Subst, &EGraph<L, N>) -> bool` and [Condition]s are conditions.
+/// The built-in conditions below, which need the e-graph, return named types instead, which implement [Display] for logging.
+pub trait Cond<L: Language, N: Analysis<L>>: 'static {
+    /// Whether the condition holds for the match `subst`.
+    fn check(&self, subst: &Subst, eg: &EGraph<L, N>) -> bool;
+}
+
+impl<T, L: Language, N: Analysis<L>> Cond<L, N> for T
+where
+    T: Fn(&Subst, &EGraph<L, N>) -> bool + 'static,
+{
+    fn check(&self, subst: &Subst, eg: &EGraph<L, N>) -> bool {
+        self(subst, eg)
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Cond<L, N> for Condition {
+    fn check(&self, subst: &Subst, _eg: &EGraph<L, N>) -> bool {
+        Condition::check(self, subst)
+    }
+}
+
+fn lookup<'a>(subst: &'a Subst, var: &str, cond: &dyn Display) -> &'a AppliedId {
+    subst.get(var).unwrap_or_else(|| {
+        panic!("condition `{cond}` refers to `?{var}`, but it is missing in the `subst`")
+    })
+}
+
+/// The slot `slot` doesn't occur in `?var`.
+///
+/// This is a closure, so it can be used with [Rewrite::new_if] or called directly.
+/// The printable version of it is the [Condition] `$x # ?var`.
+pub fn slot_free_in<L: Language, N: Analysis<L>>(
+    slot: &str,
+    var: &str,
+) -> impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static {
+    let c = Condition::Fresh(Slot::named(slot), var.to_string());
+    move |subst, _| c.check(subst)
+}
+
+/// The slot `slot` occurs in `?var`. Printed as `(not $x # ?var)`.
+pub fn slot_occurs_in(slot: &str, var: &str) -> Condition {
+    let fresh = Condition::Fresh(Slot::named(slot), var.to_string());
+    Condition::Not(Box::new(fresh))
+}
+
+/// `?a` and `?b` are equal in the e-graph (see [EGraph::eq]). Printed as `?a = ?b`.
+pub fn equal(a: &str, b: &str) -> Equal {
+    Equal(a.to_string(), b.to_string())
+}
+
+/// See [equal].
+pub struct Equal(String, String);
+
+impl<L: Language, N: Analysis<L>> Cond<L, N> for Equal {
+    fn check(&self, subst: &Subst, eg: &EGraph<L, N>) -> bool {
+        eg.eq(lookup(subst, &self.0, self), lookup(subst, &self.1, self))
+    }
+}
+
+impl Display for Equal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "?{} = ?{}", self.0, self.1)
+    }
+}
+
+/// The e-class of `?var` contains an e-node matching `pattern`. Printed as `?var contains pattern`.
+///
+/// The pattern variables of `pattern` are independent of the variables of the rule.
+pub fn contains_match<L: Language>(var: &str, pattern: &str) -> ContainsMatch<L> {
+    ContainsMatch {
+        var: var.to_string(),
+        pattern: CompiledPattern::new(Pattern::parse(pattern).unwrap()),
+    }
+}
+
+/// See [contains_match].
+pub struct ContainsMatch<L: Language> {
+    var: String,
+    pattern: CompiledPattern<L>,
+}
+
+impl<L: Language + 'static, N: Analysis<L>> Cond<L, N> for ContainsMatch<L> {
+    fn check(&self, subst: &Subst, eg: &EGraph<L, N>) -> bool {
+        let x = lookup(subst, &self.var, self);
+        !self.pattern.run(eg, x.id).is_empty()
+    }
+}
+
+impl<L: Language> Display for ContainsMatch<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "?{} contains {}", self.var, self.pattern.pattern())
+    }
+}
+
+/// The e-class of `?var` has at most `n` slots. Printed as `|slots(?var)| <= n`.
+pub fn max_slots(var: &str, n: usize) -> MaxSlots {
+    MaxSlots(var.to_string(), n)
+}
+
+/// See [max_slots].
+pub struct MaxSlots(String, usize);
+
+impl<L: Language, N: Analysis<L>> Cond<L, N> for MaxSlots {
+    fn check(&self, subst: &Subst, _eg: &EGraph<L, N>) -> bool {
+        lookup(subst, &self.0, self).slots().len() <= self.1
+    }
+}
+
+impl Display for MaxSlots {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "|slots(?{})| <= {}", self.0, self.1)
+    }
+}
+
+/// The analysis data of `?var` satisfies `pred`. Printed as `name(?var)`.
+pub fn analysis<D, F>(var: &str, name: &str, pred: F) -> AnalysisSatisfies<F>
+where
+    F: Fn(&D) -> bool,
+{
+    AnalysisSatisfies {
+        var: var.to_string(),
+        name: name.to_string(),
+        pred,
+    }
+}
+
+/// See [analysis].
+pub struct AnalysisSatisfies<F> {
+    var: String,
+    name: String,
+    pred: F,
+}
+
+impl<L, N, F> Cond<L, N> for AnalysisSatisfies<F>
+where
+    L: Language,
+    N: Analysis<L>,
+    F: Fn(&N::Data) -> bool + 'static,
+{
+    fn check(&self, subst: &Subst, eg: &EGraph<L, N>) -> bool {
+        let x = lookup(subst, &self.var, self);
+        (self.pred)(eg.analysis_data(x.id))
+    }
+}
+
+impl<F> Display for AnalysisSatisfies<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(?{})", self.name, self.var)
+    }
+}
+
+/// Either `x` or `y` holds.
+///
+/// Like [slot_free_in], the combinators [or], [and] and [not] return closures.
+/// Printable combinations of freshness conditions are [Condition]s, e.g. `(or $x # ?a $x # ?b)`.
+pub fn or<L: Language, N: Analysis<L>>(
+    x: impl Cond<L, N>,
+    y: impl Cond<L, N>,
+) -> impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static {
+    move |subst, eg| x.check(subst, eg) || y.check(subst, eg)
+}
+
+/// Both `x` and `y` hold.
+pub fn and<L: Language, N: Analysis<L>>(
+    x: impl Cond<L, N>,
+    y: impl Cond<L, N>,
+) -> impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static {
+    move |subst, eg| x.check(subst, eg) && y.check(subst, eg)
+}
+
+/// `x` doesn't hold.
+pub fn not<L: Language, N: Analysis<L>>(
+    x: impl Cond<L, N>,
+) -> impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static {
+    move |subst, eg| !x.check(subst, eg)
+}
@@ -0,0 +1,55 @@
+use crate::*;
+
+/// A side condition of a rule, that can be printed and parsed.
+///
+/// ```text
+/// $x # ?b              the slot `$x` is fresh for `?b`, i.e. it doesn't occur free in `?b`.
+/// (not c)
+/// (and c1 c2 ...)
+/// (or c1 c2 ...)
+/// ```
+///
+/// Conditions can be attached to the rhs of a rule with `where`, e.g. `?f where $x # ?f`.
+/// Multiple conditions `where c1, c2` all have to hold.
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub enum Condition {
+    Fresh(Slot, String),
+    Not(Box<Condition>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+impl Condition {
+    /// Evaluates the condition for the match `subst`.
+    pub fn check(&self, subst: &Subst) -> bool {
+        match self {
+            Condition::Fresh(s, v) => {
+                let Some(x) = subst.get(v) else {
+                    panic!("condition `{self}` refers to `?{v}`, but it is missing in the `subst`")
+                };
+                !x.slots().contains(s)
+            }
+            Condition::Not(c) => !c.check(subst),
+            Condition::And(cs) => cs.iter().all(|c| c.check(subst)),
+            Condition::Or(cs) => cs.iter().any(|c| c.check(subst)),
+        }
+    }
+
+    // the pattern variables this condition refers to.
+    pub(crate) fn pvars(&self) -> Vec<String> {
+        match self {
+            Condition::Fresh(_, v) => vec![v.clone()],
+            Condition::Not(c) => c.pvars(),
+            Condition::And(cs) | Condition::Or(cs) => cs.iter().flat_map(|c| c.pvars()).collect(),
+        }
+    }
+
+    // the slots this condition refers to.
+    pub(crate) fn slots(&self) -> Vec<Slot> {
+        match self {
+            Condition::Fresh(s, _) => vec![*s],
+            Condition::Not(c) => c.slots(),
+            Condition::And(cs) | Condition::Or(cs) => cs.iter().flat_map(|c| c.slots()).collect(),
+        }
+    }
+}
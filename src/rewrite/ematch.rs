@@ -1,115 +1,466 @@
 use crate::*;
 
-pub type Subst = HashMap<String, AppliedId>;
+/// The result of e-matching a pattern.
+///
+/// It maps each pattern variable `?x` to its e-class, and can be used like a `HashMap<String, AppliedId>`.
+/// Additionally, it stores the operators bound by operator variables `(?op ...)`, and the matched e-class itself.
+///
+/// It used to be an alias of `HashMap<String, AppliedId>`, code using it as such can convert with [From] and [Into].
+#[derive(Clone, Default, Debug)]
+pub struct Subst {
+    vars: HashMap<String, AppliedId>,
 
-#[derive(Default, Clone)]
-struct State {
-    // uses egraph slots.
-    partial_subst: Subst,
+    // the operators bound by operator variables, using `AppliedId::null()` as children.
+    ops: HashMap<String, Vec<SyntaxElem>>,
 
-    // maps from the egraph slots to the pattern slots.
-    partial_slotmap: SlotMap,
+    // the e-class matched by the whole pattern, if it came from e-matching a single pattern.
+    root: Option<AppliedId>,
 }
 
-pub fn ematch_all<L: Language, N: Analysis<L>>(
-    eg: &EGraph<L, N>,
-    pattern: &Pattern<L>,
-) -> Vec<Subst> {
-    let mut out = Vec::new();
-    for i in eg.ids() {
-        let i = eg.mk_sem_identity_applied_id(i);
-        out.extend(
-            ematch_impl(pattern, State::default(), i, eg)
-                .into_iter()
-                .map(final_subst),
-        );
+impl Subst {
+    /// The e-class matched by the whole pattern.
+    ///
+    /// This is `None` for [Subst]s that come from a [MultiPattern], or that were built manually.
+    pub fn root(&self) -> Option<&AppliedId> {
+        self.root.as_ref()
+    }
+
+    /// The operator bound by the operator variable `?op`, with `AppliedId::null()` as children.
+    pub fn operator<L: Language>(&self, op: &str) -> Option<L> {
+        L::from_syntax(self.ops.get(op)?)
+    }
+
+    pub fn insert_operator<L: Language>(&mut self, op: String, node: &L) {
+        self.ops.insert(op, nullify_app_ids(node).to_syntax());
     }
-    out
 }
 
-// `i` uses egraph slots instead of pattern slots.
-fn ematch_impl<L: Language, N: Analysis<L>>(
-    pattern: &Pattern<L>,
-    st: State,
-    i: AppliedId,
-    eg: &EGraph<L, N>,
-) -> Vec<State> {
-    match &pattern {
-        Pattern::PVar(v) => {
-            let mut st = st;
-            if let Some(j) = st.partial_subst.get(v) {
-                if !eg.eq(&i, j) {
-                    return Vec::new();
-                }
-            } else {
-                st.partial_subst.insert(v.clone(), i);
-            }
-            vec![st]
+impl std::ops::Deref for Subst {
+    type Target = HashMap<String, AppliedId>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.vars
+    }
+}
+
+impl std::ops::DerefMut for Subst {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.vars
+    }
+}
+
+impl IntoIterator for Subst {
+    type Item = (String, AppliedId);
+    type IntoIter = <HashMap<String, AppliedId> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vars.into_iter()
+    }
+}
+
+impl From<HashMap<String, AppliedId>> for Subst {
+    fn from(vars: HashMap<String, AppliedId>) -> Self {
+        Subst {
+            vars,
+            ..Default::default()
         }
-        Pattern::ENode(n, children) => {
-            let mut out = Vec::new();
-            for nn in eg.enodes_applied(&i) {
-                let d = std::mem::discriminant(n);
-                let dd = std::mem::discriminant(&nn);
-                if d != dd {
-                    continue;
-                };
+    }
+}
+
+impl From<Subst> for HashMap<String, AppliedId> {
+    fn from(subst: Subst) -> Self {
+        subst.vars
+    }
+}
 
-                ematch_node(&st, eg, &n, children, &mut out, &nn);
+impl FromIterator<(String, AppliedId)> for Subst {
+    fn from_iter<T: IntoIterator<Item = (String, AppliedId)>>(iter: T) -> Self {
+        Subst {
+            vars: iter.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+// The index of a register of the e-matching machine.
+type Reg = usize;
+
+/// A [Pattern] compiled into a sequence of instructions for e-matching, similar to egg's e-matching machine.
+///
+/// Pattern variables are resolved to registers at compile time, the [Subst]s are only built for complete matches.
+/// Or-patterns are compiled into one instruction sequence for each alternative.
+pub struct CompiledPattern<L: Language> {
+    pattern: Pattern<L>,
+    programs: Vec<Program<L>>,
+}
+
+struct Program<L: Language> {
+    // the alternative of the pattern, that is matched by this program.
+    pattern: Pattern<L>,
+    instructions: Vec<Instruction<L>>,
+
+    // the register holding the value of each pattern variable.
+    vars: Vec<(String, Reg)>,
+
+    // the operator register holding the value of each operator variable.
+    ops: Vec<(String, Reg)>,
+}
+
+enum Instruction<L: Language> {
+    // iterates over the e-nodes in the class `reg[i]` that have the shape `node`,
+    // and writes their children into the registers starting at `out`.
+    Bind {
+        node: L,
+        i: Reg,
+        out: Reg,
+    },
+
+    // iterates over the e-nodes in the class `reg[i]` that have `arity` children,
+    // writes their children into the registers starting at `out`, and their operator into the operator register `op`.
+    // If `op` is already bound, the operator has to be equal, including its (egraph-based) slots.
+    BindOp {
+        arity: usize,
+        i: Reg,
+        out: Reg,
+        op: Reg,
+        bound: bool,
+    },
+
+    // requires `reg[i]` and `reg[j]` to be equal.
+    Compare {
+        i: Reg,
+        j: Reg,
+    },
+
+    // requires `reg[i]` not to use any of the pattern slots `forbidden`.
+    CheckSlots {
+        i: Reg,
+        forbidden: Vec<Slot>,
+    },
+}
+
+// the state of the e-matching machine.
+// `regs` use egraph slots, `slotmap` maps them to the pattern slots.
+struct MachineState<'a, L: Language> {
+    regs: &'a mut Vec<AppliedId>,
+    ops: &'a mut Vec<L>,
+    slotmap: &'a SlotMap,
+}
+
+impl<L: Language> CompiledPattern<L> {
+    pub fn new(pattern: Pattern<L>) -> Self {
+        let programs = alternatives(&pattern)
+            .into_iter()
+            .map(Program::new)
+            .collect();
+        CompiledPattern { pattern, programs }
+    }
+
+    pub fn pattern(&self) -> &Pattern<L> {
+        &self.pattern
+    }
+
+    /// Finds all matches in the e-graph.
+    ///
+    /// If the pattern is rooted in an e-node, only the classes containing its operator are visited.
+    pub fn search<N: Analysis<L>>(&self, eg: &EGraph<L, N>) -> Vec<Subst> {
+        let mut out = Vec::new();
+        for prog in &self.programs {
+            let roots = match &prog.pattern {
+                Pattern::ENode(n, _) => eg.classes_with_operator(n),
+                _ => eg.ids(),
+            };
+            for i in roots {
+                prog.run(eg, i, &mut out);
             }
-            out
         }
-        Pattern::Subst(..) => panic!(),
+        out
+    }
+
+    /// Finds all matches whose root is the e-class `i`.
+    pub fn run<N: Analysis<L>>(&self, eg: &EGraph<L, N>, i: Id) -> Vec<Subst> {
+        let mut out = Vec::new();
+        for prog in &self.programs {
+            prog.run(eg, i, &mut out);
+        }
+        out
     }
 }
 
-fn ematch_node<L: Language, N: Analysis<L>>(
-    st: &State,
-    eg: &EGraph<L, N>,
-    n: &L,
-    children: &[Pattern<L>],
-    out: &mut Vec<State>,
-    nn: &L,
-) {
-    'nodeloop: for n2 in eg.get_group_compatible_weak_variants(&nn) {
-        if CHECKS {
-            assert_eq!(&nullify_app_ids(n), n);
+// expands the or-patterns in `pat`, returning patterns without or-patterns.
+fn alternatives<L: Language>(pat: &Pattern<L>) -> Vec<Pattern<L>> {
+    let product = |children: &[Pattern<L>]| {
+        let mut acc: Vec<Vec<Pattern<L>>> = vec![Vec::new()];
+        for c in children {
+            let alts = alternatives(c);
+            acc = acc
+                .into_iter()
+                .flat_map(|prefix| {
+                    alts.iter().map(move |a| {
+                        let mut v = prefix.clone();
+                        v.push(a.clone());
+                        v
+                    })
+                })
+                .collect();
         }
+        acc
+    };
+    match pat {
+        Pattern::ENode(n, children) => product(children)
+            .into_iter()
+            .map(|ch| Pattern::ENode(n.clone(), ch))
+            .collect(),
+        Pattern::OpVar(v, children) => product(children)
+            .into_iter()
+            .map(|ch| Pattern::OpVar(v.clone(), ch))
+            .collect(),
+        Pattern::Or(alts) => alts.iter().flat_map(alternatives).collect(),
+        _ => vec![pat.clone()],
+    }
+}
+
+impl<L: Language> Program<L> {
+    fn new(pattern: Pattern<L>) -> Self {
+        let mut p = Program {
+            pattern: pattern.clone(),
+            instructions: Vec::new(),
+            vars: Vec::new(),
+            ops: Vec::new(),
+        };
+        let mut next_reg = 1;
+        p.compile(&pattern, 0, &mut next_reg, &[]);
+        p
+    }
 
-        let clear_n2 = nullify_app_ids(&n2);
-        // We can use weak_shape here, as the inputs are nullified
-        // i.e. they only have id0() without slot args, so there are no permutations possible.
-        let (n_sh, _) = n.weak_shape();
-        let (clear_n2_sh, _) = clear_n2.weak_shape();
-        if n_sh != clear_n2_sh {
-            continue 'nodeloop;
+    // `bound` are the slots bound by the e-nodes above `pattern`.
+    fn compile(&mut self, pattern: &Pattern<L>, i: Reg, next_reg: &mut Reg, bound: &[Slot]) {
+        match pattern {
+            Pattern::PVar(v) => {
+                if let Some((_, j)) = self.vars.iter().find(|(w, _)| w == v) {
+                    self.instructions.push(Instruction::Compare { i: *j, j: i });
+                } else {
+                    self.vars.push((v.clone(), i));
+                }
+            }
+            Pattern::HOVar(v, args) => {
+                let allowed: Vec<Slot> = args.iter().flat_map(HOArg::slots).collect();
+                let forbidden: Vec<Slot> = bound
+                    .iter()
+                    .copied()
+                    .filter(|s| !allowed.contains(s))
+                    .collect();
+                if !forbidden.is_empty() {
+                    self.instructions
+                        .push(Instruction::CheckSlots { i, forbidden });
+                }
+                self.compile(&Pattern::PVar(v.clone()), i, next_reg, bound);
+            }
+            Pattern::ENode(n, children) => {
+                if CHECKS {
+                    assert_eq!(&nullify_app_ids(n), n);
+                }
+
+                // We can use weak_shape here, as the node is nullified
+                // i.e. it only has id0() without slot args, so there are no permutations possible.
+                let out = *next_reg;
+                *next_reg += children.len();
+                self.instructions.push(Instruction::Bind {
+                    node: n.clone(),
+                    i,
+                    out,
+                });
+                let mut bound = bound.to_vec();
+                bound.extend(n.private_slot_occurrences());
+                for (k, child) in children.iter().enumerate() {
+                    self.compile(child, out + k, next_reg, &bound);
+                }
+            }
+            Pattern::OpVar(v, children) => {
+                let out = *next_reg;
+                *next_reg += children.len();
+                let (op, bound_op) = match self.ops.iter().find(|(w, _)| w == v) {
+                    Some((_, op)) => (*op, true),
+                    None => {
+                        let op = self.ops.len();
+                        self.ops.push((v.clone(), op));
+                        (op, false)
+                    }
+                };
+                self.instructions.push(Instruction::BindOp {
+                    arity: children.len(),
+                    i,
+                    out,
+                    op,
+                    bound: bound_op,
+                });
+                for (k, child) in children.iter().enumerate() {
+                    self.compile(child, out + k, next_reg, bound);
+                }
+            }
+            Pattern::Wildcard => {}
+            Pattern::Or(_) => unreachable!("or-patterns are expanded by `alternatives`"),
+            Pattern::Subst(..) => panic!("Can't match against a substitution"),
         }
+    }
 
-        let mut st = st.clone();
+    fn run<N: Analysis<L>>(&self, eg: &EGraph<L, N>, i: Id, out: &mut Vec<Subst>) {
+        let mut regs = vec![eg.mk_sem_identity_applied_id(i)];
+        let mut ops = Vec::new();
+        let st = MachineState {
+            regs: &mut regs,
+            ops: &mut ops,
+            slotmap: &SlotMap::new(),
+        };
+        self.run_impl(0, st, eg, out);
+    }
 
-        for (x, y) in clear_n2
-            .all_slot_occurrences()
-            .into_iter()
-            .zip(n.all_slot_occurrences().into_iter())
-        {
-            if !try_insert_compatible_slotmap_bij(x, y, &mut st.partial_slotmap) {
-                continue 'nodeloop;
+    fn run_impl<N: Analysis<L>>(
+        &self,
+        pc: usize,
+        st: MachineState<L>,
+        eg: &EGraph<L, N>,
+        out: &mut Vec<Subst>,
+    ) {
+        let Some(instr) = self.instructions.get(pc) else {
+            out.push(self.final_subst(&st));
+            return;
+        };
+
+        match instr {
+            Instruction::Compare { i, j } => {
+                if eg.eq(&st.regs[*i], &st.regs[*j]) {
+                    self.run_impl(pc + 1, st, eg, out);
+                }
+            }
+            Instruction::CheckSlots { i, forbidden } => {
+                let uses_forbidden = st.regs[*i]
+                    .slots()
+                    .into_iter()
+                    .any(|s| matches!(st.slotmap.get(s), Some(p) if forbidden.contains(&p)));
+                if !uses_forbidden {
+                    self.run_impl(pc + 1, st, eg, out);
+                }
+            }
+            Instruction::Bind { node, i, out: o } => {
+                let d = std::mem::discriminant(node);
+                let (node_sh, _) = node.weak_shape();
+                for nn in eg.enodes_applied(&st.regs[*i]) {
+                    if d != std::mem::discriminant(&nn) {
+                        continue;
+                    }
+
+                    'nodeloop: for n2 in eg.get_group_compatible_weak_variants(&nn) {
+                        let clear_n2 = nullify_app_ids(&n2);
+                        let (clear_n2_sh, _) = clear_n2.weak_shape();
+                        if node_sh != clear_n2_sh {
+                            continue 'nodeloop;
+                        }
+
+                        let mut slotmap = st.slotmap.clone();
+                        for (x, y) in clear_n2
+                            .all_slot_occurrences()
+                            .into_iter()
+                            .zip(node.all_slot_occurrences().into_iter())
+                        {
+                            if !try_insert_compatible_slotmap_bij(x, y, &mut slotmap) {
+                                continue 'nodeloop;
+                            }
+                        }
+
+                        st.regs.truncate(*o);
+                        st.regs
+                            .extend(n2.applied_id_occurrences().into_iter().cloned());
+                        let st = MachineState {
+                            regs: &mut *st.regs,
+                            ops: &mut *st.ops,
+                            slotmap: &slotmap,
+                        };
+                        self.run_impl(pc + 1, st, eg, out);
+                    }
+                }
+            }
+            Instruction::BindOp {
+                arity,
+                i,
+                out: o,
+                op,
+                bound,
+            } => {
+                for nn in eg.enodes_applied(&st.regs[*i]) {
+                    if nn.applied_id_occurrences().len() != *arity {
+                        continue;
+                    }
+
+                    for n2 in eg.get_group_compatible_weak_variants(&nn) {
+                        let clear_n2 = nullify_app_ids(&n2);
+                        if *bound && st.ops[*op] != clear_n2 {
+                            continue;
+                        }
+
+                        st.regs.truncate(*o);
+                        st.regs
+                            .extend(n2.applied_id_occurrences().into_iter().cloned());
+                        if !*bound {
+                            st.ops.truncate(*op);
+                            st.ops.push(clear_n2);
+                        }
+                        let st = MachineState {
+                            regs: &mut *st.regs,
+                            ops: &mut *st.ops,
+                            slotmap: st.slotmap,
+                        };
+                        self.run_impl(pc + 1, st, eg, out);
+                    }
+                }
             }
         }
+    }
 
-        let mut acc = vec![st];
-        for (sub_id, sub_pat) in n2.applied_id_occurrences().into_iter().zip(children.iter()) {
-            let mut next = Vec::new();
-            for a in acc {
-                next.extend(ematch_impl(sub_pat, a, sub_id.clone(), eg));
+    fn final_subst(&self, st: &MachineState<L>) -> Subst {
+        let mut slotmap = st.slotmap.clone();
+        let mut subst = Subst::default();
+
+        // The registers use `egraph`-based slot names.
+        // The subst uses `pattern`-based slot names.
+        let mut rename = |x: &AppliedId| {
+            // All slots that are not covered by the pattern, need a fresh new name.
+            for s in x.slots() {
+                if !slotmap.contains_key(s) {
+                    slotmap.insert(s, Slot::fresh());
+                }
             }
-            acc = next;
+            x.apply_slotmap(&slotmap)
+        };
+
+        for (v, r) in &self.vars {
+            subst.insert(v.clone(), rename(&st.regs[*r]));
         }
+        subst.root = Some(rename(&st.regs[0]));
 
-        out.extend(acc);
+        // the slots of an operator, including the ones it binds, are renamed like the slots of the registers.
+        for (v, r) in &self.ops {
+            let mut n = st.ops[*r].clone();
+            for s in n.all_slot_occurrences_mut() {
+                if !slotmap.contains_key(*s) {
+                    slotmap.insert(*s, Slot::fresh());
+                }
+                *s = slotmap[*s];
+            }
+            subst.insert_operator(v.clone(), &n);
+        }
+
+        subst
     }
 }
 
+pub fn ematch_all<L: Language, N: Analysis<L>>(
+    eg: &EGraph<L, N>,
+    pattern: &Pattern<L>,
+) -> Vec<Subst> {
+    CompiledPattern::new(pattern.clone()).search(eg)
+}
+
 pub(crate) fn nullify_app_ids<L: Language>(l: &L) -> L {
     let mut l = l.clone();
     for x in l.applied_id_occurrences_mut() {
@@ -118,6 +469,15 @@ pub(crate) fn nullify_app_ids<L: Language>(l: &L) -> L {
     l
 }
 
+// The operator of an e-node: its children are nullified, and all its slots are replaced by `$0`.
+pub(crate) fn operator_key<L: Language>(l: &L) -> L {
+    let mut l = nullify_app_ids(l);
+    for x in l.all_slot_occurrences_mut() {
+        *x = Slot::numeric(0);
+    }
+    l
+}
+
 fn try_insert_compatible_slotmap_bij(k: Slot, v: Slot, map: &mut SlotMap) -> bool {
     if let Some(v_old) = map.get(k) {
         if v_old != v {
@@ -127,25 +487,3 @@ fn try_insert_compatible_slotmap_bij(k: Slot, v: Slot, map: &mut SlotMap) -> boo
     map.insert(k, v);
     map.is_bijection()
 }
-
-fn final_subst(s: State) -> Subst {
-    let State {
-        partial_subst: mut subst,
-        partial_slotmap: mut slotmap,
-    } = s;
-
-    // Previously, the subst uses `egraph`-based slot names.
-    // Afterwards, the subst uses `pattern`-based slot names.
-    for (_, v) in subst.iter_mut() {
-        // All slots that are not covered by the pattern, need a fresh new name.
-        for s in v.slots() {
-            if !slotmap.contains_key(s) {
-                slotmap.insert(s, Slot::fresh());
-            }
-        }
-
-        *v = v.apply_slotmap(&slotmap);
-    }
-
-    subst
-}
@@ -0,0 +1,120 @@
+use crate::*;
+
+/// Generates capture-avoiding explicit-substitution rules for any language with a variable node and a let node.
+///
+/// `var` declares the variable node, e.g. `"(var $1)"`, and `let_` declares the let node, e.g. `"(let $1 ?b ?t)"`,
+/// which stands for the substitution `b[(var $1) := t]`.
+/// Both have to use the slot `$1` for the variable, and `let_` has to use `?b` for the body and `?t` for the substituted term.
+///
+/// The generated rules are
+/// - `let-var-same`: `(let $1 (var $1) ?t) => ?t`
+/// - `let-unused`: `(let $1 ?b ?t) => ?b`, if `$1` doesn't occur in `?b`.
+/// - `let-push`: pushes the let into every e-node of `?b` (other than `(var $1)`), wrapping those children which use `$1`.
+///   The binders of these e-nodes are renamed to fresh slots, so that they can't capture the free slots of `?t`.
+///
+/// Typically, these rules are used together with a rule like `(app (lam $1 ?b) ?t) => (let $1 ?b ?t)`.
+///
+/// Returns an error if `var` or `let_` can't be parsed or don't have the required shape.
+pub fn explicit_subst_rules<L: Language + 'static, N: Analysis<L> + 'static>(
+    var: &str,
+    let_: &str,
+) -> Result<Vec<Rewrite<L, N>>, RewriteError> {
+    let x = Slot::numeric(1);
+    let var = Pattern::<L>::parse(var).map_err(RewriteError::Parse)?;
+    let let_ = Pattern::<L>::parse(let_).map_err(RewriteError::Parse)?;
+    if !matches!(&var, Pattern::ENode(n, c) if c.is_empty() && n.all_slot_occurrences() == [x]) {
+        return Err(RewriteError::InvalidVarNode(var.to_string()));
+    }
+    let Pattern::ENode(let_node, let_children) = &let_ else {
+        return Err(RewriteError::InvalidLetNode(let_.to_string()));
+    };
+    let mut pvars: Vec<_> = let_children
+        .iter()
+        .map(|c| match c {
+            Pattern::PVar(v) => v.as_str(),
+            _ => "",
+        })
+        .collect();
+    pvars.sort();
+    if pvars != ["b", "t"] || let_node.all_slot_occurrences() != [x] {
+        return Err(RewriteError::InvalidLetNode(let_.to_string()));
+    }
+
+    let var_same = {
+        let children = let_children
+            .iter()
+            .map(|c| match c {
+                Pattern::PVar(v) if v == "b" => var.clone(),
+                c => c.clone(),
+            })
+            .collect();
+        Pattern::ENode(let_node.clone(), children)
+    };
+    let unused = ConditionalApplier {
+        cond: slot_free_in("1", "b"),
+        applier: Pattern::PVar("b".to_string()),
+    };
+
+    Ok(vec![
+        Rewrite::from_parts(
+            "let-var-same",
+            CompiledPattern::new(var_same),
+            Pattern::PVar("t".to_string()),
+        ),
+        Rewrite::from_parts("let-unused", CompiledPattern::new(let_.clone()), unused),
+        Rewrite::from_parts(
+            "let-push",
+            CompiledPattern::new(let_.clone()),
+            LetPush { let_ },
+        ),
+    ])
+}
+
+// The applier of the `let-push` rule.
+struct LetPush<L: Language> {
+    let_: Pattern<L>,
+}
+
+impl<L: Language, N: Analysis<L>> Applier<L, N> for LetPush<L> {
+    fn apply_one(
+        &self,
+        eg: &mut EGraph<L, N>,
+        subst: &Subst,
+        lhs: Option<&Pattern<L>>,
+        rule: &str,
+    ) -> bool {
+        let x = Slot::numeric(1);
+        let b = eg.find_applied_id(&subst["b"]);
+        if !b.slots().contains(&x) {
+            return false;
+        }
+        let orig = instantiate_lhs(eg, lhs.unwrap_or(&self.let_), subst);
+
+        let mut changed = false;
+        // `enodes_applied` gives fresh names to the binders, so they can't capture anything.
+        for mut n in eg.enodes_applied(&b) {
+            // `$1` occurs directly in the e-node (e.g. `(var $1)`), so the let can't be pushed any further.
+            if nullify_app_ids(&n).public_slot_occurrences().contains(&x) {
+                continue;
+            }
+            let children: Vec<AppliedId> =
+                n.applied_id_occurrences().into_iter().cloned().collect();
+            let children: Vec<AppliedId> = children
+                .into_iter()
+                .map(|c| {
+                    if !c.slots().contains(&x) {
+                        return c;
+                    }
+                    let s = [("b".to_string(), c), ("t".to_string(), subst["t"].clone())];
+                    pattern_subst(eg, &self.let_, &s.into_iter().collect())
+                })
+                .collect();
+            for (r, c) in n.applied_id_occurrences_mut().into_iter().zip(children) {
+                *r = c;
+            }
+            let new = eg.add_syn(n);
+            changed |= eg.union_justified(&orig, &new, Some(rule.to_string()));
+        }
+        changed
+    }
+}
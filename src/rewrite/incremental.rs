@@ -0,0 +1,99 @@
+use crate::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// A [Searcher] for a [Pattern] that only re-matches the parts of the e-graph that changed since its last search.
+///
+/// The matches are cached per root e-class.
+/// An e-class is re-matched, if some e-class within the height of the pattern below it gained e-nodes, slots, symmetries or was merged (see [EGraph::changed_since]).
+/// The results coincide with the results of [ematch_all] (up to the choice of fresh slots).
+///
+/// The cache belongs to a single e-graph, so an `IncrementalPattern` should not be used to search different e-graphs.
+pub struct IncrementalPattern<L: Language> {
+    pattern: CompiledPattern<L>,
+    height: usize,
+    cache: RefCell<Option<MatchCache>>,
+}
+
+struct MatchCache {
+    // the timestamp of the e-graph at the last search.
+    timestamp: usize,
+    matches: BTreeMap<Id, Vec<Subst>>,
+}
+
+impl<L: Language> IncrementalPattern<L> {
+    pub fn new(pattern: Pattern<L>) -> Self {
+        let height = pattern_height(&pattern);
+        IncrementalPattern {
+            pattern: CompiledPattern::new(pattern),
+            height,
+            cache: RefCell::new(None),
+        }
+    }
+
+    /// Forgets all cached matches, so that the next search is a full search.
+    pub fn reset(&self) {
+        *self.cache.borrow_mut() = None;
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Searcher<L, N> for IncrementalPattern<L> {
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst> {
+        let mut cache = self.cache.borrow_mut();
+        let cache = match &mut *cache {
+            Some(cache) => {
+                cache.matches.retain(|i, _| eg.is_alive(*i));
+                for i in affected_roots(eg, cache.timestamp, self.height) {
+                    cache.matches.insert(i, self.pattern.run(eg, i));
+                }
+                cache.timestamp = eg.timestamp();
+                cache
+            }
+            None => cache.insert(MatchCache {
+                timestamp: eg.timestamp(),
+                matches: eg
+                    .ids()
+                    .into_iter()
+                    .map(|i| (i, self.pattern.run(eg, i)))
+                    .collect(),
+            }),
+        };
+
+        cache.matches.values().flatten().cloned().collect()
+    }
+
+    fn get_pattern(&self) -> Option<&Pattern<L>> {
+        Some(self.pattern.pattern())
+    }
+}
+
+// the e-classes which have a changed e-class at most `height` steps below them.
+fn affected_roots<L: Language, N: Analysis<L>>(
+    eg: &EGraph<L, N>,
+    t: usize,
+    height: usize,
+) -> HashSet<Id> {
+    let mut out: HashSet<Id> = eg.changed_since(t).into_iter().collect();
+    let mut frontier: Vec<Id> = out.iter().copied().collect();
+    for _ in 0..height {
+        for i in std::mem::take(&mut frontier) {
+            for j in eg.parent_classes(i) {
+                if out.insert(j) {
+                    frontier.push(j);
+                }
+            }
+        }
+    }
+    out
+}
+
+// the maximal number of e-node patterns on a path from the root to a leaf.
+fn pattern_height<L: Language>(pat: &Pattern<L>) -> usize {
+    match pat {
+        Pattern::ENode(_, children) => 1 + children.iter().map(pattern_height).max().unwrap_or(0),
+        Pattern::OpVar(_, children) => 1 + children.iter().map(pattern_height).max().unwrap_or(0),
+        Pattern::Or(alts) => alts.iter().map(pattern_height).max().unwrap_or(0),
+        Pattern::PVar(_) | Pattern::HOVar(..) | Pattern::Wildcard => 0,
+        Pattern::Subst(..) => panic!("Can't match against a substitution"),
+    }
+}
@@ -1,5 +1,6 @@
 use crate::*;
 use std::any::Any;
+use std::time::Instant;
 
 mod ematch;
 pub use ematch::*;
@@ -10,17 +11,51 @@ pub use pattern::*;
 mod subst_method;
 pub use subst_method::*;
 
+mod explicit_subst;
+pub use explicit_subst::*;
+
 mod multipat;
 pub use multipat::*;
 
+mod searcher;
+pub use searcher::*;
+
+mod incremental;
+pub use incremental::*;
+
+mod applier;
+pub use applier::*;
+
+mod condition;
+pub use condition::*;
+
+mod cond;
+pub use cond::*;
+
+mod rule_file;
+pub use rule_file::*;
+
+mod validate;
+pub use validate::*;
+
+mod term;
+pub use term::*;
+
+mod unify;
+pub use unify::*;
+
 /// An equational rewrite rule.
 pub struct Rewrite<L: Language, N: Analysis<L> = ()> {
-    pub(crate) searcher: Box<dyn Fn(&EGraph<L, N>) -> Box<dyn Any>>,
+    pub(crate) name: String,
+    pub(crate) searcher: Box<dyn Fn(&EGraph<L, N>) -> SearchMatches>,
     pub(crate) applier: Box<dyn Fn(Box<dyn Any>, &mut EGraph<L, N>)>,
+    pub(crate) term_rule: Option<TermRule<L>>,
 }
 
 /// Use this type when you want to build your own [Rewrite].
 ///
+/// For most custom rules it is simpler to implement a [Searcher] or an [Applier], and use [Rewrite::from_parts] instead.
+///
 /// The type parameter `T` can be anything you want, as long as the `searcher` creates it, and the `applier` consumes it.
 ///
 /// In most cases, `T` is a [Subst].
@@ -31,20 +66,130 @@ pub struct RewriteT<L: Language, N: Analysis<L> = (), T: Any = ()> {
 
 impl<L: Language + 'static, N: Analysis<L> + 'static, T: 'static> RewriteT<L, N, T> {
     /// Use this function to convert it to an actual [Rewrite].
+    ///
+    /// The resulting rule is called "custom", use [RewriteT::into_named] to give it a proper name.
+    /// A [Runner] requires distinct names, so it can only run one such rule at a time.
     pub fn into(self) -> Rewrite<L, N> {
+        self.into_named("custom")
+    }
+
+    /// Converts it to an actual [Rewrite] with the name `rule`.
+    ///
+    /// The matches of the resulting rule aren't counted, see [SearchMatches::count].
+    /// Use [RewriteT::into_counted] if `T` implements [MatchCount].
+    pub fn into_named(self, rule: &str) -> Rewrite<L, N> {
+        self.into_rewrite(rule, |_| None)
+    }
+
+    fn into_rewrite(self, rule: &str, count: fn(&T) -> Option<usize>) -> Rewrite<L, N> {
         let searcher = self.searcher;
         let applier = self.applier;
         Rewrite {
-            searcher: Box::new(move |eg| Box::new((*searcher)(eg))),
+            name: rule.to_string(),
+            searcher: Box::new(move |eg| {
+                let t = (*searcher)(eg);
+                SearchMatches {
+                    num_matches: count(&t),
+                    data: Box::new(t),
+                }
+            }),
             applier: Box::new(move |t, eg| (*applier)(any_to_t(t), eg)),
+            term_rule: None,
         }
     }
 }
 
+impl<L: Language + 'static, N: Analysis<L> + 'static, T: MatchCount + 'static> RewriteT<L, N, T> {
+    /// Converts it to an actual [Rewrite] with the name `rule`, whose matches are counted by [MatchCount].
+    pub fn into_counted(self, rule: &str) -> Rewrite<L, N> {
+        self.into_rewrite(rule, |t| Some(t.num_matches()))
+    }
+}
+
 pub fn any_to_t<T: Any>(t: Box<dyn Any>) -> T {
     *t.downcast().unwrap()
 }
 
+/// Counts how many matches the searcher of a [RewriteT] found.
+///
+/// This is used by [Scheduler]s to limit explosive rules.
+pub trait MatchCount {
+    fn num_matches(&self) -> usize;
+}
+
+impl<T> MatchCount for Vec<T> {
+    fn num_matches(&self) -> usize {
+        self.len()
+    }
+}
+
+/// The (type-erased) matches found by the searcher of a [Rewrite].
+///
+/// They can only be consumed by the applier of the same [Rewrite].
+pub struct SearchMatches {
+    data: Box<dyn Any>,
+    num_matches: Option<usize>,
+}
+
+impl SearchMatches {
+    /// The number of matches, as counted by [MatchCount].
+    ///
+    /// This is `None` for rules which don't count their matches, e.g. a [RewriteT] converted by [RewriteT::into_named].
+    pub fn count(&self) -> Option<usize> {
+        self.num_matches
+    }
+
+    /// The number of matches, or 0 if they aren't counted, see [SearchMatches::count].
+    pub fn len(&self) -> usize {
+        self.num_matches.unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_matches == Some(0)
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Rewrite<L, N> {
+    /// The name of this rule.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runs the searcher of this rule.
+    pub fn search(&self, eg: &EGraph<L, N>) -> SearchMatches {
+        (*self.searcher)(eg)
+    }
+
+    /// Runs the applier of this rule on matches previously found by [Rewrite::search].
+    pub fn apply(&self, matches: SearchMatches, eg: &mut EGraph<L, N>) {
+        (*self.applier)(matches.data, eg)
+    }
+
+    // declares that this rule is `lhs => rhs where cond`, so that it can also be applied to terms.
+    pub(crate) fn with_term_rule(
+        mut self,
+        lhs: Pattern<L>,
+        rhs: Pattern<L>,
+        cond: Option<Condition>,
+    ) -> Self {
+        self.term_rule = Some(TermRule { lhs, rhs, cond });
+        self
+    }
+}
+
+// panics if two of the `rewrites` have the same name.
+// Rules are identified by their names, e.g. by the [BackoffScheduler] and in [RuleStats].
+pub(crate) fn assert_distinct_names<L: Language, N: Analysis<L>>(rewrites: &[Rewrite<L, N>]) {
+    let mut names = HashSet::default();
+    for rw in rewrites {
+        assert!(
+            names.insert(rw.name()),
+            "the rule name `{}` is used twice, but rules need distinct names",
+            rw.name()
+        );
+    }
+}
+
 /// Applies each given rewrite rule to the E-Graph once.
 /// Returns an indicator for whether the e-graph changed as a result.
 pub fn apply_rewrites<L: Language, N: Analysis<L>>(
@@ -53,57 +198,175 @@ pub fn apply_rewrites<L: Language, N: Analysis<L>>(
 ) -> bool {
     let prog = eg.progress();
 
-    let ts: Vec<Box<dyn Any>> = rewrites.iter().map(|rw| (*rw.searcher)(eg)).collect();
-    for (rw, t) in rewrites.iter().zip(ts.into_iter()) {
-        (*rw.applier)(t, eg);
+    let ts: Vec<SearchMatches> = rewrites.iter().map(|rw| rw.search(eg)).collect();
+    for (rw, t) in rewrites.iter().zip(ts) {
+        rw.apply(t, eg);
     }
 
     prog != eg.progress()
 }
 
+/// Like [apply_rewrites], but additionally returns the [RuleStats] of each rule, by its name.
+///
+/// Panics if two of the `rewrites` have the same name, as their statistics couldn't be told apart.
+pub fn apply_rewrites_with_stats<L: Language, N: Analysis<L>>(
+    eg: &mut EGraph<L, N>,
+    rewrites: &[Rewrite<L, N>],
+) -> (bool, HashMap<String, RuleStats>) {
+    assert_distinct_names(rewrites);
+    let prog = eg.progress();
+    let mut stats: HashMap<String, RuleStats> = HashMap::default();
+
+    let mut ts = Vec::new();
+    for rw in rewrites {
+        let start = Instant::now();
+        let t = rw.search(eg);
+        let s = stats.entry(rw.name().to_string()).or_default();
+        s.search_time += start.elapsed();
+        s.matches += t.len();
+        ts.push(t);
+    }
+    for (rw, t) in rewrites.iter().zip(ts.into_iter()) {
+        let start = Instant::now();
+        let unions = eg.union_count();
+        rw.apply(t, eg);
+        let s = stats.get_mut(rw.name()).unwrap();
+        s.apply_time += start.elapsed();
+        s.unions += eg.union_count() - unions;
+    }
+
+    (prog != eg.progress(), stats)
+}
+
 impl<L: Language + 'static, N: Analysis<L> + 'static> Rewrite<L, N> {
     /// Create a rewrite rule by specifing a left- and right-hand side of your equation.
+    ///
+    /// The right-hand side can be followed by [Condition]s, e.g. `?f where $x # ?f`.
+    ///
+    /// Panics if the rule is invalid, see [Rewrite::try_new].
     pub fn new(rule: &str, a: &str, b: &str) -> Self {
-        Self::new_if(rule, a, b, |_, _| true)
+        Self::try_new(rule, a, b).unwrap_or_else(|e| panic!("invalid rule `{rule}`: {e}"))
     }
 
-    /// Create a conditional rewrite rule.
+    /// Like [Rewrite::new], but returns an error if the rule can't be parsed or has scoping problems,
+    /// e.g. if the rhs uses a pattern variable or slot that isn't bound on the lhs.
+    pub fn try_new(rule: &str, a: &str, b: &str) -> Result<Self, RewriteError> {
+        let lhs = Pattern::parse(a).map_err(RewriteError::Parse)?;
+        let (rhs, cond) = parse_rhs(b).map_err(RewriteError::Parse)?;
+        validate_rule(&lhs, &rhs, cond.as_ref())?;
+
+        let where_cond = cond.clone();
+        let a = CompiledPattern::new(lhs.clone());
+        let b = ConditionalApplier {
+            cond: move |subst: &Subst, _: &EGraph<L, N>| {
+                where_cond.as_ref().is_none_or(|c| c.check(subst))
+            },
+            applier: rhs.clone(),
+        };
+        // without a closure condition, the rule can also be applied to terms.
+        Ok(Self::from_parts_unchecked(rule, a, b).with_term_rule(lhs, rhs, cond))
+    }
+
+    /// Create a conditional rewrite rule, whose condition is a closure.
     pub fn new_if(
         rule: &str,
         a: &str,
         b: &str,
         cond: impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static,
     ) -> Self {
-        let a = Pattern::parse(a).unwrap();
-        let b = Pattern::parse(b).unwrap();
-        let rule = rule.to_string();
-        let a2 = a.clone();
-        RewriteT {
-            searcher: Box::new(move |eg| ematch_all(eg, &a)),
-            applier: Box::new(move |substs, eg| {
-                Self::apply_substs_cond(substs, &cond, &a2, &b, &rule, eg)
-            }),
-        }
-        .into()
+        Self::new_cond(rule, a, b, cond)
+    }
+
+    /// Create a conditional rewrite rule, whose condition is any [Cond], e.g. `and(equal("a", "b"), max_slots("a", 1))`.
+    pub fn new_cond(rule: &str, a: &str, b: &str, cond: impl Cond<L, N>) -> Self {
+        Self::try_new_cond(rule, a, b, cond)
+            .unwrap_or_else(|e| panic!("invalid rule `{rule}`: {e}"))
     }
 
-    fn apply_substs_cond(
-        substs: Vec<Subst>,
-        cond: &(impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static),
-        a: &Pattern<L>,
-        b: &Pattern<L>,
+    /// Like [Rewrite::new_cond], but returns an error for invalid rules, see [Rewrite::try_new].
+    pub fn try_new_cond(
         rule: &str,
-        eg: &mut EGraph<L, N>,
-    ) {
-        for subst in substs {
-            if cond(&subst, eg) {
-                eg.union_instantiations(a, b, &subst, Some(rule.to_string()));
-            }
+        a: &str,
+        b: &str,
+        cond: impl Cond<L, N>,
+    ) -> Result<Self, RewriteError> {
+        let a = Pattern::parse(a).map_err(RewriteError::Parse)?;
+        let (b, where_cond) = parse_rhs(b).map_err(RewriteError::Parse)?;
+        validate_rule(&a, &b, where_cond.as_ref())?;
+
+        let cond = move |subst: &Subst, eg: &EGraph<L, N>| {
+            where_cond.as_ref().is_none_or(|c| c.check(subst)) && cond.check(subst, eg)
+        };
+        let a = CompiledPattern::new(a);
+        let b = ConditionalApplier { cond, applier: b };
+        Ok(Self::from_parts_unchecked(rule, a, b))
+    }
+
+    /// Create a rewrite rule, whose left-hand side is a [MultiPattern], and whose right-hand side is a [MultiApplier].
+    ///
+    /// For example `Rewrite::new_multi("fg", "?a == (f ?x), ?b == (g ?x)", "?a == ?b")`.
+    ///
+    /// Panics if the rule is invalid, see [Rewrite::try_new_multi].
+    pub fn new_multi(rule: &str, a: &str, b: &str) -> Self {
+        Self::try_new_multi(rule, a, b).unwrap_or_else(|e| panic!("invalid rule `{rule}`: {e}"))
+    }
+
+    /// Like [Rewrite::new_multi], but returns an error if the rule can't be parsed or has scoping problems,
+    /// e.g. if the applier uses a pattern variable that isn't bound by the multi-pattern.
+    pub fn try_new_multi(rule: &str, a: &str, b: &str) -> Result<Self, RewriteError> {
+        let a = MultiPattern::parse(a).map_err(RewriteError::Parse)?;
+        let b = MultiApplier::parse(b).map_err(RewriteError::Parse)?;
+        validate_multi_rule(&a, &b.eqs)?;
+        Ok(Self::from_parts_unchecked(rule, a, b))
+    }
+
+    /// Create a rewrite rule from any [Searcher] and [Applier].
+    ///
+    /// Panics if the `applier` rejects the pattern of the `searcher`, see [Rewrite::try_from_parts].
+    pub fn from_parts(
+        rule: &str,
+        searcher: impl Searcher<L, N> + 'static,
+        applier: impl Applier<L, N> + 'static,
+    ) -> Self {
+        Self::try_from_parts(rule, searcher, applier)
+            .unwrap_or_else(|e| panic!("invalid rule `{rule}`: {e}"))
+    }
+
+    /// Like [Rewrite::from_parts], but returns an error if [Applier::validate] fails.
+    ///
+    /// For example, a [Pattern] applier is checked like the rhs of [Rewrite::try_new],
+    /// and it requires a searcher with a single pattern, i.e. not a [MultiPattern].
+    pub fn try_from_parts(
+        rule: &str,
+        searcher: impl Searcher<L, N> + 'static,
+        applier: impl Applier<L, N> + 'static,
+    ) -> Result<Self, RewriteError> {
+        applier.validate(searcher.get_pattern())?;
+        Ok(Self::from_parts_unchecked(rule, searcher, applier))
+    }
+
+    // like `from_parts`, for rules that were already validated.
+    fn from_parts_unchecked(
+        rule: &str,
+        searcher: impl Searcher<L, N> + 'static,
+        applier: impl Applier<L, N> + 'static,
+    ) -> Self {
+        let lhs = searcher.get_pattern().cloned();
+        let rule2 = rule.to_string();
+        RewriteT {
+            searcher: Box::new(move |eg| searcher.search(eg)),
+            applier: Box::new(move |substs: Vec<Subst>, eg| {
+                for subst in substs {
+                    applier.apply_one(eg, &subst, lhs.as_ref(), &rule2);
+                }
+            }),
         }
+        .into_counted(rule)
     }
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A Progress Measure to check saturation of an e-graph with.
 pub struct ProgressMeasure {
     /// How many classes that were allocated in this e-graph. This measure is strictly growing.
@@ -139,34 +402,10 @@ macro_rules! rw {
     };
 
     ($name:expr; $lhs:expr => $rhs:expr, if !$cond:expr) => {
-        Rewrite::new_if($name, $lhs, $rhs, not($cond))
+        Rewrite::new_cond($name, $lhs, $rhs, not($cond))
     };
 
     ($name:expr; $lhs:expr => $rhs:expr, if $cond:expr) => {
-        Rewrite::new_if($name, $lhs, $rhs, $cond)
+        Rewrite::new_cond($name, $lhs, $rhs, $cond)
     };
 }
-
-pub trait Cond<L, N>: Fn(&Subst, &EGraph<L, N>) -> bool + 'static {}
-impl<T, L: Language, N: Analysis<L>> Cond<L, N> for T where
-    T: Fn(&Subst, &EGraph<L, N>) -> bool + 'static
-{
-}
-
-pub fn slot_free_in<L: Language, N: Analysis<L>>(slot: &str, var: &str) -> impl Cond<L, N> {
-    let s: Slot = Slot::named(slot);
-    let var = var.to_string();
-    move |subst, _| !subst[&*var].slots().contains(&s)
-}
-
-pub fn or<L: Language, N: Analysis<L>>(x: impl Cond<L, N>, y: impl Cond<L, N>) -> impl Cond<L, N> {
-    move |subst, eg| x(subst, eg) || y(subst, eg)
-}
-
-pub fn and<L: Language, N: Analysis<L>>(x: impl Cond<L, N>, y: impl Cond<L, N>) -> impl Cond<L, N> {
-    move |subst, eg| x(subst, eg) && y(subst, eg)
-}
-
-pub fn not<L: Language, N: Analysis<L>>(x: impl Cond<L, N>) -> impl Cond<L, N> {
-    move |subst, eg| !x(subst, eg)
-}
@@ -3,13 +3,153 @@ use crate::*;
 pub type PVar = String; // TODO this should be interned, or better: index-based.
 
 pub struct MultiPattern<L: Language> {
-    // covers equations like `= ?a (f ?b ?c)`.
+    // covers equations like `?a == (f ?b ?c)`.
     // we require them to have nesting depth exactly one.
-    // This is not a restriction:
-    // - nesting depth 0 `(= ?a ?b)` can be solved via pre-processing, and
-    // - nesting depth >1 `(= ?a (f (f ?x)))` can be solved via flattening `(= ?a (f ?b)), (= ?b (f ?x))`.
+    // This is not a restriction, see `MultiPattern::from_equations`:
+    // - nesting depth 0 `?a == ?b` is solved via pre-processing (see `aliases`), and
+    // - nesting depth >1 `?a == (f (f ?x))` is solved via flattening `?a == (f ?b), ?b == (f ?x)`.
     // variables are allowed to come up multiple times on the left and right.
     pub(crate) pats: Vec<(PVar, L, Vec<PVar>)>,
+
+    // (x, y) expresses that `?x` is equal to `?y`.
+    // `?x` doesn't come up in `pats`, it only gets its value from `?y` at the end of matching.
+    pub(crate) aliases: Vec<(PVar, PVar)>,
+}
+
+impl<L: Language> MultiPattern<L> {
+    /// Builds a MultiPattern from equations `?a == pat`.
+    ///
+    /// Nested patterns are flattened by introducing fresh pattern variables. These will also be part of the resulting [Subst]s.
+    pub fn from_equations(eqs: Vec<(PVar, Pattern<L>)>) -> Result<Self, ParseError> {
+        let mut used: HashSet<PVar> = HashSet::default();
+        for (v, pat) in &eqs {
+            used.insert(v.clone());
+            used.extend(pattern_pvars(pat));
+        }
+
+        // the depth-0 equations form equivalence classes of variables.
+        let mut alias: HashMap<PVar, PVar> = HashMap::default();
+        for (v, pat) in &eqs {
+            if let Pattern::PVar(w) = pat {
+                let v = find_alias(v, &alias);
+                let w = find_alias(w, &alias);
+                if v != w {
+                    alias.insert(w, v);
+                }
+            }
+        }
+
+        let mut flattener = Flattener {
+            alias: &alias,
+            used,
+            next_fresh: 0,
+            pats: Vec::new(),
+        };
+        for (v, pat) in &eqs {
+            if let Pattern::ENode(..) = pat {
+                flattener.flatten(find_alias(v, &alias), pat)?;
+            } else if let Pattern::Subst(..) | Pattern::HOVar(..) = pat {
+                return Err(ParseError::SubstInMultiPattern(pat.to_string()));
+            } else if let Pattern::Wildcard | Pattern::Or(_) | Pattern::OpVar(..) = pat {
+                return Err(ParseError::UnsupportedInMultiPattern(pat.to_string()));
+            }
+        }
+        let pats = flattener.pats;
+
+        let mut aliases: Vec<(PVar, PVar)> = alias
+            .keys()
+            .map(|x| (x.clone(), find_alias(x, &alias)))
+            .collect();
+        aliases.sort();
+
+        Ok(MultiPattern { pats, aliases })
+    }
+
+    // the pattern variables bound by every match.
+    pub(crate) fn pvars(&self) -> HashSet<PVar> {
+        let mut out = HashSet::default();
+        for (v, _, children) in &self.pats {
+            out.insert(v.clone());
+            out.extend(children.iter().cloned());
+        }
+        out.extend(self.aliases.iter().map(|(x, _)| x.clone()));
+        out
+    }
+}
+
+fn find_alias(v: &PVar, alias: &HashMap<PVar, PVar>) -> PVar {
+    let mut v = v;
+    while let Some(w) = alias.get(v) {
+        v = w;
+    }
+    v.clone()
+}
+
+struct Flattener<'a, L: Language> {
+    alias: &'a HashMap<PVar, PVar>,
+    used: HashSet<PVar>,
+    next_fresh: usize,
+    pats: Vec<(PVar, L, Vec<PVar>)>,
+}
+
+impl<L: Language> Flattener<'_, L> {
+    // adds the equation `?v == pat` to `pats`, where `pat` is an e-node pattern.
+    fn flatten(&mut self, v: PVar, pat: &Pattern<L>) -> Result<(), ParseError> {
+        let Pattern::ENode(n, children) = pat else {
+            unreachable!()
+        };
+
+        let mut child_vars = Vec::new();
+        let mut nested = Vec::new();
+        for c in children {
+            match c {
+                Pattern::PVar(x) => child_vars.push(find_alias(x, self.alias)),
+                Pattern::ENode(..) => {
+                    let x = self.fresh_pvar();
+                    child_vars.push(x.clone());
+                    nested.push((x, c));
+                }
+                Pattern::Subst(..) | Pattern::HOVar(..) => {
+                    return Err(ParseError::SubstInMultiPattern(c.to_string()));
+                }
+                Pattern::Wildcard | Pattern::Or(_) | Pattern::OpVar(..) => {
+                    return Err(ParseError::UnsupportedInMultiPattern(c.to_string()));
+                }
+            }
+        }
+        self.pats.push((v, n.clone(), child_vars));
+
+        for (x, c) in nested {
+            self.flatten(x, c)?;
+        }
+        Ok(())
+    }
+
+    fn fresh_pvar(&mut self) -> PVar {
+        loop {
+            let x = format!("_{}", self.next_fresh);
+            self.next_fresh += 1;
+            if !self.used.contains(&x) {
+                return x;
+            }
+        }
+    }
+}
+
+fn pattern_pvars<L: Language>(pat: &Pattern<L>) -> Vec<PVar> {
+    match pat {
+        Pattern::ENode(_, children) => children.iter().flat_map(pattern_pvars).collect(),
+        Pattern::PVar(v) => vec![v.clone()],
+        Pattern::Subst(b, sigma) => subst_parts(b, sigma).flat_map(pattern_pvars).collect(),
+        Pattern::HOVar(v, _) => vec![v.clone()],
+        Pattern::Wildcard => Vec::new(),
+        Pattern::Or(children) => children.iter().flat_map(pattern_pvars).collect(),
+        Pattern::OpVar(v, children) => {
+            let mut out = vec![v.clone()];
+            out.extend(children.iter().flat_map(pattern_pvars));
+            out
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -30,7 +170,7 @@ struct MultiState {
     slot_uf: HashMap<Slot, Slot>,
 }
 
-pub fn multi_ematch<L: Language>(pat: &MultiPattern<L>, eg: &EGraph<L>) -> Vec<Subst> {
+pub fn multi_ematch<L: Language, N: Analysis<L>>(pat: &MultiPattern<L>, eg: &EGraph<L, N>) -> Vec<Subst> {
     let mut states: Vec<MultiState> = vec![MultiState {
         pattern_slots: HashSet::default(),
         diseq_constraints: HashMap::default(),
@@ -44,19 +184,29 @@ pub fn multi_ematch<L: Language>(pat: &MultiPattern<L>, eg: &EGraph<L>) -> Vec<S
         }
     }
 
+    for (x, y) in &pat.aliases {
+        for st in std::mem::take(&mut states) {
+            for mut st in multi_ematch_step_class(y, st, eg) {
+                let v = st.subst[y].clone();
+                st.subst.insert(x.clone(), v);
+                states.push(st);
+            }
+        }
+    }
+
     states.into_iter().map(|x| x.subst).collect()
 }
 
-fn multi_ematch_step<L: Language>(pv: &PVar, node: &L, children: &[PVar], mut state: MultiState, eg: &EGraph<L>) -> Vec<MultiState> {
+fn multi_ematch_step<L: Language, N: Analysis<L>>(pv: &PVar, node: &L, children: &[PVar], mut state: MultiState, eg: &EGraph<L, N>) -> Vec<MultiState> {
     let mut out = Vec::new();
-    for state in multi_ematch_step_class(pv, node, children, state, eg) {
+    for state in multi_ematch_step_class(pv, state, eg) {
         out.extend(multi_ematch_step_node(pv, node, children, state, eg));
     }
     out
 }
 
 
-fn multi_ematch_step_class<L: Language>(pv: &PVar, node: &L, children: &[PVar], mut state: MultiState, eg: &EGraph<L>) -> Vec<MultiState> {
+fn multi_ematch_step_class<L: Language, N: Analysis<L>>(pv: &PVar, state: MultiState, eg: &EGraph<L, N>) -> Vec<MultiState> {
     if state.subst.contains_key(pv) { return vec![state] }
 
     let mut out = Vec::new();
@@ -72,7 +222,7 @@ fn multi_ematch_step_class<L: Language>(pv: &PVar, node: &L, children: &[PVar],
     out
 }
 
-fn multi_ematch_step_node<L: Language>(pv: &PVar, node: &L, children: &[PVar], mut state: MultiState, eg: &EGraph<L>) -> Vec<MultiState> {
+fn multi_ematch_step_node<L: Language, N: Analysis<L>>(pv: &PVar, node: &L, children: &[PVar], mut state: MultiState, eg: &EGraph<L, N>) -> Vec<MultiState> {
     let gid = &state.subst[pv];
     let mut out = Vec::new();
 
@@ -112,7 +262,7 @@ fn matches_raw<L: Language>(n1: &L, n2: &L, mut st: MultiState) -> Option<MultiS
     Some(st)
 }
 
-fn extend_subst<L: Language>(pv: &PVar, x: AppliedId, mut st: MultiState, eg: &EGraph<L>) -> Vec<MultiState> {
+fn extend_subst<L: Language, N: Analysis<L>>(pv: &PVar, x: AppliedId, mut st: MultiState, eg: &EGraph<L, N>) -> Vec<MultiState> {
     if let Some(y) = st.subst.get(pv).cloned() {
         unify(&x, &y, st, eg)
     } else {
@@ -121,7 +271,7 @@ fn extend_subst<L: Language>(pv: &PVar, x: AppliedId, mut st: MultiState, eg: &E
     }
 }
 
-fn unify<L: Language>(x: &AppliedId, y: &AppliedId, mut st: MultiState, eg: &EGraph<L>) -> Vec<MultiState> {
+fn unify<L: Language, N: Analysis<L>>(x: &AppliedId, y: &AppliedId, mut st: MultiState, eg: &EGraph<L, N>) -> Vec<MultiState> {
     let x = &state_appid_find(x.clone(), &st);
     let y = &state_appid_find(y.clone(), &st);
 
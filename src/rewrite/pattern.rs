@@ -5,10 +5,104 @@ use crate::*;
 ///
 /// - It supports pattern-variables `?x` to match against anything.
 /// - It supports (on the rhs) substitutions `b[x := t]` to substitute natively.
+///   Simultaneous substitutions are written `b[x := t, y := u]`, whereas `b[x := t][y := u]` substitutes one after the other.
+/// - It supports higher-order pattern-variables `?f[$x]` and `?f[(var $x)]`, see [HOArg].
+/// - It supports (on the lhs) wildcards `_`, which match anything without binding it, and or-patterns `(| p1 p2)`,
+///   which match if one of their alternatives matches, and bind the pattern variables of that alternative.
+/// - It supports operator variables `(?op ?a ?b)`, which match any e-node with the given number of children.
+///   On the rhs, they are instantiated to the same operator, including its slots.
+///   Repeated occurrences of `?op` only match e-nodes whose slots are equal as well.
 pub enum Pattern<L: Language> {
     ENode(L, Vec<Pattern<L>>),
-    PVar(String),                                             // ?x
-    Subst(Box<Pattern<L>>, Box<Pattern<L>>, Box<Pattern<L>>), // Subst(b, x, t) means `b[x := t]`
+    PVar(String),                                          // ?x
+    Subst(Box<Pattern<L>>, Vec<(Pattern<L>, Pattern<L>)>), // Subst(b, [(x, t), ..]) means `b[x := t, ..]`
+    HOVar(String, Vec<HOArg<L>>),                          // ?f[$x, (var $y)]
+    Wildcard,                                              // _
+    Or(Vec<Pattern<L>>),                                   // (| p1 p2)
+    OpVar(String, Vec<Pattern<L>>),                        // (?op ?a ?b)
+}
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+/// An argument of a higher-order pattern-variable `?f[a, ...]`.
+///
+/// On the lhs, the arguments are the parameters of `?f`.
+/// Among the slots bound by the lhs (e.g. by a `lam`), `?f` only matches terms which use the slots of its parameters.
+/// So `(lam $x (app ?f[] (var $x)))` only matches, if `?f` doesn't use `$x`.
+///
+/// On the rhs, `?f[a, ...]` instantiates the parameters of `?f` with the arguments:
+/// - a slot parameter `$x` can be instantiated with a slot `$y`, by renaming.
+/// - a term parameter `(var $x)` can be instantiated with any term, using the configured [SubstMethod].
+pub enum HOArg<L: Language> {
+    Slot(Slot),
+    Term(Pattern<L>),
+}
+
+impl<L: Language> HOArg<L> {
+    // the slots occurring in this argument.
+    pub(crate) fn slots(&self) -> Vec<Slot> {
+        match self {
+            HOArg::Slot(s) => vec![*s],
+            HOArg::Term(t) => pattern_slots(t),
+        }
+    }
+}
+
+// `b` and the sides of the substitution `sigma` of `b[sigma]`.
+pub(crate) fn subst_parts<'a, L: Language>(
+    b: &'a Pattern<L>,
+    sigma: &'a [(Pattern<L>, Pattern<L>)],
+) -> impl Iterator<Item = &'a Pattern<L>> {
+    std::iter::once(b).chain(sigma.iter().flat_map(|(x, t)| [x, t]))
+}
+
+pub(crate) fn pattern_slots<L: Language>(pat: &Pattern<L>) -> Vec<Slot> {
+    match pat {
+        Pattern::ENode(n, children) => {
+            let mut out = n.all_slot_occurrences();
+            out.extend(children.iter().flat_map(pattern_slots));
+            out
+        }
+        Pattern::PVar(_) => Vec::new(),
+        Pattern::Subst(b, sigma) => subst_parts(b, sigma).flat_map(pattern_slots).collect(),
+        Pattern::HOVar(_, args) => args.iter().flat_map(HOArg::slots).collect(),
+        Pattern::Wildcard => Vec::new(),
+        Pattern::Or(children) | Pattern::OpVar(_, children) => {
+            children.iter().flat_map(pattern_slots).collect()
+        }
+    }
+}
+
+// Whether `pat` describes a single term (given a Subst), i.e. it has no wildcards or or-patterns.
+pub(crate) fn is_instantiable<L: Language>(pat: &Pattern<L>) -> bool {
+    match pat {
+        Pattern::ENode(_, children) | Pattern::OpVar(_, children) => {
+            children.iter().all(is_instantiable)
+        }
+        Pattern::PVar(_) | Pattern::HOVar(..) => true,
+        Pattern::Subst(b, sigma) => subst_parts(b, sigma).all(is_instantiable),
+        Pattern::Wildcard | Pattern::Or(_) => false,
+    }
+}
+
+// The parameters of the higher-order pattern-variables in `pat`.
+pub(crate) fn ho_params<L: Language>(pat: &Pattern<L>) -> HashMap<String, Vec<HOArg<L>>> {
+    fn rec<L: Language>(pat: &Pattern<L>, out: &mut HashMap<String, Vec<HOArg<L>>>) {
+        match pat {
+            Pattern::ENode(_, children) => children.iter().for_each(|c| rec(c, out)),
+            Pattern::PVar(_) => {}
+            Pattern::Subst(b, sigma) => subst_parts(b, sigma).for_each(|p| rec(p, out)),
+            Pattern::HOVar(f, args) => {
+                out.entry(f.clone()).or_insert_with(|| args.clone());
+            }
+            Pattern::Wildcard => {}
+            Pattern::Or(children) | Pattern::OpVar(_, children) => {
+                children.iter().for_each(|c| rec(c, out))
+            }
+        }
+    }
+    let mut out = HashMap::default();
+    rec(pat, &mut out);
+    out
 }
 
 // We write this as pattern[subst] for short.
@@ -16,6 +110,17 @@ pub fn pattern_subst<L: Language, N: Analysis<L>>(
     eg: &mut EGraph<L, N>,
     pattern: &Pattern<L>,
     subst: &Subst,
+) -> AppliedId {
+    pattern_subst_with_params(eg, pattern, subst, &ho_params(pattern))
+}
+
+// Like `pattern_subst`, but takes the parameters of the higher-order pattern-variables from `params`.
+// These typically come from the lhs of a rule.
+pub(crate) fn pattern_subst_with_params<L: Language, N: Analysis<L>>(
+    eg: &mut EGraph<L, N>,
+    pattern: &Pattern<L>,
+    subst: &Subst,
+    params: &HashMap<String, Vec<HOArg<L>>>,
 ) -> AppliedId {
     match &pattern {
         Pattern::ENode(n, children) => {
@@ -25,7 +130,7 @@ pub fn pattern_subst<L: Language, N: Analysis<L>>(
                 assert_eq!(children.len(), refs.len());
             }
             for i in 0..refs.len() {
-                *(refs[i]) = pattern_subst(eg, &children[i], subst);
+                *(refs[i]) = pattern_subst_with_params(eg, &children[i], subst, params);
             }
             eg.add_syn(n)
         }
@@ -35,18 +140,180 @@ pub fn pattern_subst<L: Language, N: Analysis<L>>(
                 panic!("encountered `?{v}` in pattern, but it is missing in the `subst`")
             })
             .clone(),
-        Pattern::Subst(b, x, t) => {
-            let b = pattern_subst(eg, &*b, subst);
-            let x = pattern_subst(eg, &*x, subst);
-            let t = pattern_subst(eg, &*t, subst);
-
-            // temporary swap-out so that we can access both the e-graph and the subst-method fully.
-            let mut method = eg.subst_method.take().unwrap();
-            let out = method.subst(b, x, t, eg);
-            eg.subst_method = Some(method);
-            out
+        Pattern::Subst(b, sigma) => {
+            let b = pattern_subst_with_params(eg, b, subst, params);
+            let sigma: Vec<_> = sigma
+                .iter()
+                .map(|(x, t)| {
+                    let x = pattern_subst_with_params(eg, x, subst, params);
+                    let t = pattern_subst_with_params(eg, t, subst, params);
+                    (x, t)
+                })
+                .collect();
+            run_subst_method(eg, b, &sigma)
+        }
+        Pattern::OpVar(op, children) => {
+            let Some(mut n) = subst.operator::<L>(op) else {
+                panic!("encountered `?{op}` in pattern, but it is missing in the `subst`")
+            };
+            let mut refs: Vec<&mut _> = n.applied_id_occurrences_mut();
+            assert_eq!(
+                children.len(),
+                refs.len(),
+                "operator `?{op}` is used with a different number of children"
+            );
+            for i in 0..refs.len() {
+                *(refs[i]) = pattern_subst_with_params(eg, &children[i], subst, params);
+            }
+            eg.add_syn(n)
         }
+        Pattern::Wildcard | Pattern::Or(_) => {
+            panic!("Can't instantiate `{pattern}`, wildcards and or-patterns are only allowed on the lhs")
+        }
+        Pattern::HOVar(f, args) => {
+            let b = pattern_subst_with_params(eg, &Pattern::PVar(f.clone()), subst, params);
+            let Some(ps) = params.get(f) else {
+                panic!("`?{f}[..]` needs to be bound by a higher-order pattern variable on the lhs")
+            };
+            if ps == args {
+                return b;
+            }
+            assert_eq!(
+                ps.len(),
+                args.len(),
+                "`?{f}[..]` is instantiated with the wrong number of arguments"
+            );
+            instantiate_ho(eg, b, ps, args, subst, params)
+        }
+    }
+}
+
+// Instantiates the lhs `pat` of a rule for a match `subst`.
+// If `pat` doesn't describe a single term, the matched e-class is used.
+pub(crate) fn instantiate_lhs<L: Language, N: Analysis<L>>(
+    eg: &mut EGraph<L, N>,
+    pat: &Pattern<L>,
+    subst: &Subst,
+) -> AppliedId {
+    if is_instantiable(pat) {
+        pattern_subst(eg, pat, subst)
+    } else {
+        let Some(root) = subst.root() else {
+            panic!("`{pat}` can't be instantiated, as the `subst` doesn't know the matched e-class")
+        };
+        root.clone()
+    }
+}
+
+fn run_subst_method<L: Language, N: Analysis<L>>(
+    eg: &mut EGraph<L, N>,
+    b: AppliedId,
+    sigma: &[(AppliedId, AppliedId)],
+) -> AppliedId {
+    if let Some(out) = rename_vars(eg, &b, sigma) {
+        return out;
+    }
+
+    // temporary swap-out so that we can access both the e-graph and the subst-method fully.
+    let mut method = eg.subst_method.take().unwrap();
+    let out = match sigma {
+        [(x, t)] => method.subst(b, x.clone(), t.clone(), eg),
+        _ => method.subst_many(b, sigma, eg),
+    };
+    eg.subst_method = Some(method);
+    out
+}
+
+// computes `b[sigma]` by renaming the slots of `b`, if `sigma` only substitutes variables for variables.
+// Returns `None`, if that is not the case or if the renaming would identify two slots of `b`.
+fn rename_vars<L: Language, N: Analysis<L>>(
+    eg: &mut EGraph<L, N>,
+    b: &AppliedId,
+    sigma: &[(AppliedId, AppliedId)],
+) -> Option<AppliedId> {
+    let var = eg.var_node?;
+    // only looks the variable up, so that failed attempts don't add e-nodes.
+    let as_var = |x: &AppliedId| -> Option<Slot> {
+        let [s] = x.slots().into_iter().collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        let v = eg.lookup(&var(s))?;
+        (eg.find_applied_id(&v) == eg.find_applied_id(x)).then_some(s)
+    };
+
+    let mut renaming = SlotMap::new();
+    for (x, t) in sigma {
+        let x = as_var(x)?;
+        let t = as_var(t)?;
+        // like in `b[x := t, x := u]` the first substitution wins.
+        if !renaming.contains_key(x) {
+            renaming.insert(x, t);
+        }
+    }
+
+    let mut m = SlotMap::new();
+    for s in b.slots() {
+        m.insert(s, renaming.get(s).unwrap_or(s));
+    }
+    m.is_bijection().then(|| b.apply_slotmap(&m))
+}
+
+// computes `b[ps := args]`.
+fn instantiate_ho<L: Language, N: Analysis<L>>(
+    eg: &mut EGraph<L, N>,
+    b: AppliedId,
+    ps: &[HOArg<L>],
+    args: &[HOArg<L>],
+    subst: &Subst,
+    params: &HashMap<String, Vec<HOArg<L>>>,
+) -> AppliedId {
+    // We first rename the parameters to fresh slots, so that they can't clash with the arguments.
+    let mut fresh = SlotMap::new();
+    for s in ps.iter().flat_map(HOArg::slots) {
+        if !fresh.contains_key(s) {
+            fresh.insert(s, Slot::fresh());
+        }
+    }
+    let refresh = |x: &AppliedId| {
+        let mut m = SlotMap::new();
+        for s in x.slots() {
+            m.insert(s, fresh.get(s).unwrap_or(s));
+        }
+        x.apply_slotmap(&m)
+    };
+
+    let mut b = refresh(&b);
+    let mut renaming = SlotMap::new();
+    let mut sigma = Vec::new();
+    for (p, a) in ps.iter().zip(args) {
+        match (p, a) {
+            (HOArg::Slot(p), HOArg::Slot(a)) => {
+                renaming.insert(fresh[*p], *a);
+            }
+            (HOArg::Term(p), HOArg::Term(a)) => {
+                let x = pattern_subst_with_params(eg, p, subst, params);
+                let x = refresh(&x);
+                let t = pattern_subst_with_params(eg, a, subst, params);
+                sigma.push((x, t));
+            }
+            _ => panic!(
+                "slot parameters can only be instantiated by slots, and term parameters by terms"
+            ),
+        }
+    }
+    if !sigma.is_empty() {
+        b = run_subst_method(eg, b, &sigma);
+    }
+
+    let mut m = SlotMap::new();
+    for s in b.slots() {
+        m.insert(s, renaming.get(s).unwrap_or(s));
     }
+    assert!(
+        m.is_bijection(),
+        "instantiating a higher-order pattern variable would capture a slot"
+    );
+    b.apply_slotmap(&m)
 }
 
 // TODO maybe move into EGraph API?
@@ -0,0 +1,191 @@
+use crate::*;
+use std::path::Path;
+use std::rc::Rc;
+
+/// An error while parsing a rule file, see [parse_rules].
+#[derive(Debug)]
+pub enum RuleParseError {
+    Io(std::io::Error),
+    /// An error in the given line, counting from 1.
+    Line(usize, RuleLineError),
+}
+
+/// What went wrong in a single line of a rule file.
+#[derive(Debug)]
+pub enum RuleLineError {
+    MissingName,
+    MissingArrow,
+    Pattern(ParseError),
+    Condition(Vec<Token>),
+    // `<=>` rules are also applied from right to left, so neither side may contain a substitution `b[x := t]` or `?f[..]`,
+    // and neither side may contain wildcards `_` or or-patterns `(| ..)`.
+    SubstInBidirectional,
+    /// The rule has scoping problems, see [Rewrite::try_new].
+    Invalid(RewriteError),
+}
+
+impl std::fmt::Display for RuleParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleParseError::Io(e) => write!(f, "can't read the rule file: {e}"),
+            RuleParseError::Line(i, e) => write!(f, "line {i}: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RuleParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RuleParseError::Io(e) => Some(e),
+            RuleParseError::Line(_, e) => Some(e),
+        }
+    }
+}
+
+impl std::fmt::Display for RuleLineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleLineError::MissingName => write!(f, "missing rule name"),
+            RuleLineError::MissingArrow => write!(f, "missing `=>` or `<=>`"),
+            RuleLineError::Pattern(e) => write!(f, "parse error: {e:?}"),
+            RuleLineError::Condition(toks) => write!(f, "invalid condition at {toks:?}"),
+            RuleLineError::SubstInBidirectional => {
+                write!(
+                    f,
+                    "a `<=>` rule may not contain substitutions, wildcards or or-patterns"
+                )
+            }
+            RuleLineError::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RuleLineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RuleLineError::Invalid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the rule file at `path`, see [parse_rules] for the format.
+pub fn load_rules<L: Language + 'static, N: Analysis<L> + 'static>(
+    path: impl AsRef<Path>,
+) -> Result<Vec<Rewrite<L, N>>, RuleParseError> {
+    let s = std::fs::read_to_string(path).map_err(RuleParseError::Io)?;
+    parse_rules(&s)
+}
+
+/// Parses a set of rewrite rules, one rule per line.
+///
+/// ```text
+/// // comments start with `//`.
+/// beta: (app (lam $1 ?b) ?t) => ?b[(var $1) := ?t]
+/// add-comm: (add ?a ?b) <=> (add ?b ?a)
+/// eta: (lam $1 (app ?f (var $1))) => ?f where $1 # ?f
+/// ```
+///
+/// A rule `name: lhs => rhs` rewrites from left to right.
+/// A rule `name: lhs <=> rhs` additionally generates the rule `name-rev: rhs => lhs`.
+///
+/// Rules can be followed by side conditions `where c1, c2, ...`, which all have to hold. See [Condition] for their syntax.
+/// For compatibility, `if` can be used instead of `where`, and `(slot_free_in $x ?b)` instead of `$x # ?b`.
+pub fn parse_rules<L: Language + 'static, N: Analysis<L> + 'static>(
+    s: &str,
+) -> Result<Vec<Rewrite<L, N>>, RuleParseError> {
+    let mut out = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let rws = parse_rule_line(line).map_err(|e| RuleParseError::Line(i + 1, e))?;
+        out.extend(rws);
+    }
+    Ok(out)
+}
+
+fn parse_rule_line<L: Language + 'static, N: Analysis<L> + 'static>(
+    line: &str,
+) -> Result<Vec<Rewrite<L, N>>, RuleLineError> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with("//") {
+        return Ok(Vec::new());
+    }
+
+    let Some((name, rest)) = line.split_once(':') else {
+        return Err(RuleLineError::MissingName);
+    };
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(RuleLineError::MissingName);
+    }
+
+    let (lhs, rest, bidirectional) = if let Some((l, r)) = rest.split_once("<=>") {
+        (l, r, true)
+    } else if let Some((l, r)) = rest.split_once("=>") {
+        (l, r, false)
+    } else {
+        return Err(RuleLineError::MissingArrow);
+    };
+
+    let lhs: Pattern<L> = Pattern::parse(lhs).map_err(RuleLineError::Pattern)?;
+
+    let tok = tokenize(rest).map_err(RuleLineError::Pattern)?;
+    let (rhs, tok) = parse_pattern::<L>(&tok).map_err(RuleLineError::Pattern)?;
+
+    let cond = match tok {
+        [] => None,
+        [Token::Ident(x), rest @ ..] if x == "if" || x == "where" => {
+            let cond_err = |_| RuleLineError::Condition(rest.to_vec());
+            let (c, rest) = parse_conditions(rest).map_err(cond_err)?;
+            if !rest.is_empty() {
+                return Err(RuleLineError::Condition(rest.to_vec()));
+            }
+            Some(c)
+        }
+        _ => {
+            return Err(RuleLineError::Pattern(ParseError::RemainingRest(
+                tok.to_vec(),
+            )))
+        }
+    };
+    validate_rule(&lhs, &rhs, cond.as_ref()).map_err(RuleLineError::Invalid)?;
+    if bidirectional && !contains_subst(&lhs) && !contains_subst(&rhs) {
+        validate_rule(&rhs, &lhs, cond.as_ref()).map_err(RuleLineError::Invalid)?;
+    }
+    let term_cond = cond.clone();
+    let cond = Rc::new(move |subst: &Subst| cond.as_ref().is_none_or(|c| c.check(subst)));
+
+    let mut out = Vec::new();
+    if bidirectional {
+        if contains_subst(&lhs) || contains_subst(&rhs) {
+            return Err(RuleLineError::SubstInBidirectional);
+        }
+        let cond = cond.clone();
+        let applier = ConditionalApplier {
+            cond: move |subst: &Subst, _: &EGraph<L, N>| cond(subst),
+            applier: lhs.clone(),
+        };
+        let rw = Rewrite::from_parts_unchecked(&format!("{name}-rev"), rhs.clone(), applier);
+        out.push(rw.with_term_rule(rhs.clone(), lhs.clone(), term_cond.clone()));
+    }
+
+    let applier = ConditionalApplier {
+        cond: move |subst: &Subst, _: &EGraph<L, N>| cond(subst),
+        applier: rhs.clone(),
+    };
+    let rw = Rewrite::from_parts_unchecked(name, lhs.clone(), applier);
+    out.insert(0, rw.with_term_rule(lhs, rhs, term_cond));
+
+    Ok(out)
+}
+
+fn contains_subst<L: Language>(pat: &Pattern<L>) -> bool {
+    match pat {
+        Pattern::ENode(_, children) | Pattern::OpVar(_, children) => {
+            children.iter().any(contains_subst)
+        }
+        Pattern::PVar(_) => false,
+        Pattern::Subst(..) | Pattern::HOVar(..) => true,
+        // these can't be instantiated on the rhs.
+        Pattern::Wildcard | Pattern::Or(_) => true,
+    }
+}
@@ -0,0 +1,40 @@
+use crate::*;
+
+/// The left-hand side of a [Rewrite]: finds the matches of a rule in the e-graph.
+pub trait Searcher<L: Language, N: Analysis<L>> {
+    /// Finds all matches in the e-graph.
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst>;
+
+    /// The pattern that is matched by this searcher, if there is a single one.
+    ///
+    /// [Applier]s use it to connect their right-hand side to the matched term.
+    fn get_pattern(&self) -> Option<&Pattern<L>> {
+        None
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Searcher<L, N> for Pattern<L> {
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst> {
+        ematch_all(eg, self)
+    }
+
+    fn get_pattern(&self) -> Option<&Pattern<L>> {
+        Some(self)
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Searcher<L, N> for CompiledPattern<L> {
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst> {
+        CompiledPattern::search(self, eg)
+    }
+
+    fn get_pattern(&self) -> Option<&Pattern<L>> {
+        Some(self.pattern())
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Searcher<L, N> for MultiPattern<L> {
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst> {
+        multi_ematch(self, eg)
+    }
+}
@@ -12,9 +12,44 @@ pub trait SubstMethod<L: Language, N: Analysis<L>> {
         t: AppliedId,
         eg: &mut EGraph<L, N>,
     ) -> AppliedId;
+
+    /// The simultaneous substitution `b[x1 := t1, x2 := t2, ...]`.
+    ///
+    /// The default implementation first renames each variable `xi` to a fresh variable `zi`, by giving its slots fresh names.
+    /// Then it substitutes `zi := ti` one after the other, which can't affect the other `tj`, as they don't contain any `zi`.
+    fn subst_many(
+        &mut self,
+        b: AppliedId,
+        sigma: &[(AppliedId, AppliedId)],
+        eg: &mut EGraph<L, N>,
+    ) -> AppliedId {
+        if let [(x, t)] = sigma {
+            return self.subst(b, x.clone(), t.clone(), eg);
+        }
+        let fresh: Vec<AppliedId> = sigma
+            .iter()
+            .map(|(x, _)| {
+                AppliedId::new(x.id, x.m.iter().map(|(k, _)| (k, Slot::fresh())).collect())
+            })
+            .collect();
+        let b = sigma
+            .iter()
+            .zip(&fresh)
+            .fold(b, |b, ((x, _), z)| self.subst(b, x.clone(), z.clone(), eg));
+        sigma
+            .iter()
+            .zip(fresh)
+            .fold(b, |b, ((_, t), z)| self.subst(b, z, t.clone(), eg))
+    }
+
+    /// The hit statistics, if this method caches its results (see [MemoSubst]).
+    fn cache_stats(&self) -> Option<SubstCacheStats> {
+        None
+    }
 }
 
 /// A [SubstMethod] that uses the [EGraph::get_syn_expr] of an e-class to do substitution on it.
+#[derive(Default)]
 pub struct SynExprSubst;
 
 impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for SynExprSubst {
@@ -28,13 +63,23 @@ impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for SynExprSubst {
         x: AppliedId,
         t: AppliedId,
         eg: &mut EGraph<L, N>,
+    ) -> AppliedId {
+        self.subst_many(b, &[(x, t)], eg)
+    }
+
+    fn subst_many(
+        &mut self,
+        b: AppliedId,
+        sigma: &[(AppliedId, AppliedId)],
+        eg: &mut EGraph<L, N>,
     ) -> AppliedId {
         let term = eg.get_syn_expr(&eg.synify_app_id(b));
-        do_term_subst(eg, &term, &x, &t)
+        do_term_subst(eg, &term, sigma, &SlotMap::new())
     }
 }
 
 /// A [SubstMethod] that extracts the smallest term (measured by [AstSize]) of an e-class to do substitution on it.
+#[derive(Default)]
 pub struct ExtractionSubst;
 
 impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for ExtractionSubst {
@@ -48,32 +93,192 @@ impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for ExtractionSubst {
         x: AppliedId,
         t: AppliedId,
         eg: &mut EGraph<L, N>,
+    ) -> AppliedId {
+        self.subst_many(b, &[(x, t)], eg)
+    }
+
+    fn subst_many(
+        &mut self,
+        b: AppliedId,
+        sigma: &[(AppliedId, AppliedId)],
+        eg: &mut EGraph<L, N>,
     ) -> AppliedId {
         let term = ast_size_extract::<L, N>(&b, eg);
-        do_term_subst(eg, &term, &x, &t)
+        do_term_subst(eg, &term, sigma, &SlotMap::new())
+    }
+}
+
+/// A [SubstMethod] that caches the results of the [SubstMethod] `S`.
+///
+/// The cache is keyed by the substitution `b[x := t, ...]`, where all e-classes are canonicalized by [EGraph::find_applied_id],
+/// and the slots are renamed to `$0, $1, ...` in order of occurrence.
+/// Hence a substitution is only computed once, even if it occurs with different slot names.
+/// After unions (see [EGraph::union_count]), the cached entries are re-canonicalized before the next lookup.
+///
+/// Use it like `EGraph::with_subst_method::<MemoSubst<SynExprSubst>>(analysis)`, and see [EGraph::subst_cache_stats] for the hit rate.
+#[derive(Default)]
+pub struct MemoSubst<S> {
+    inner: S,
+    cache: HashMap<SubstKey, AppliedId>,
+    // the union count of the e-graph at the last re-canonicalization.
+    canonical_at: usize,
+    stats: SubstCacheStats,
+}
+
+// `b[sigma]` in canonical form.
+type SubstKey = (AppliedId, Vec<(AppliedId, AppliedId)>);
+
+/// How often a [MemoSubst] could re-use a cached result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubstCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl SubstCacheStats {
+    /// The fraction of substitutions that were answered from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl<L, N, S> SubstMethod<L, N> for MemoSubst<S>
+where
+    L: Language,
+    N: Analysis<L>,
+    S: SubstMethod<L, N> + Default + 'static,
+{
+    fn new_boxed() -> Box<dyn SubstMethod<L, N>> {
+        Box::new(MemoSubst::<S>::default())
+    }
+
+    fn subst(
+        &mut self,
+        b: AppliedId,
+        x: AppliedId,
+        t: AppliedId,
+        eg: &mut EGraph<L, N>,
+    ) -> AppliedId {
+        self.subst_many(b, &[(x, t)], eg)
+    }
+
+    fn subst_many(
+        &mut self,
+        b: AppliedId,
+        sigma: &[(AppliedId, AppliedId)],
+        eg: &mut EGraph<L, N>,
+    ) -> AppliedId {
+        if eg.union_count() != self.canonical_at {
+            self.recanonicalize(eg);
+        }
+
+        let (key, m) = canonical_key(eg, &b, sigma);
+        if let Some(out) = self.cache.get(&key) {
+            self.stats.hits += 1;
+            return out.apply_slotmap(&m.inverse());
+        }
+        self.stats.misses += 1;
+
+        let out = match sigma {
+            [(x, t)] => self.inner.subst(b, x.clone(), t.clone(), eg),
+            _ => self.inner.subst_many(b, sigma, eg),
+        };
+        // the result only uses the slots of `b` and the `t`s, unless the inner method introduced new slots.
+        if out.slots().iter().all(|s| m.contains_key(*s)) {
+            self.cache.insert(key, out.apply_slotmap(&m));
+        }
+        out
+    }
+
+    fn cache_stats(&self) -> Option<SubstCacheStats> {
+        Some(self.stats)
     }
 }
 
-// returns re[x := t]
+impl<S> MemoSubst<S> {
+    // brings the keys of entries, whose e-classes were unified in the meantime, into canonical form again.
+    fn recanonicalize<L: Language, N: Analysis<L>>(&mut self, eg: &EGraph<L, N>) {
+        let cache = std::mem::take(&mut self.cache);
+        for ((b, sigma), out) in cache {
+            let (key, m) = canonical_key(eg, &b, &sigma);
+            let out = eg.find_applied_id(&out);
+            if out.slots().iter().all(|s| m.contains_key(*s)) {
+                self.cache.insert(key, out.apply_slotmap(&m));
+            }
+        }
+        self.canonical_at = eg.union_count();
+    }
+}
+
+// returns the canonical key of `b[sigma]`, and the renaming of its slots to `$0, $1, ...`.
+fn canonical_key<L: Language, N: Analysis<L>>(
+    eg: &EGraph<L, N>,
+    b: &AppliedId,
+    sigma: &[(AppliedId, AppliedId)],
+) -> (SubstKey, SlotMap) {
+    let b = eg.find_applied_id(b);
+    let sigma: Vec<_> = sigma
+        .iter()
+        .map(|(x, t)| (eg.find_applied_id(x), eg.find_applied_id(t)))
+        .collect();
+
+    let mut m = SlotMap::new();
+    let ids = std::iter::once(&b).chain(sigma.iter().flat_map(|(x, t)| [x, t]));
+    for s in ids.flat_map(|x| x.m.values_vec()) {
+        if !m.contains_key(s) {
+            m.insert(s, Slot::numeric(m.len() as u32));
+        }
+    }
+
+    let key = (
+        b.apply_slotmap(&m),
+        sigma
+            .iter()
+            .map(|(x, t)| (x.apply_slotmap(&m), t.apply_slotmap(&m)))
+            .collect(),
+    );
+    (key, m)
+}
+
+// returns re[sigma], where the bound slots of `re` are renamed by `renaming`.
+//
+// Every binder is renamed to a fresh slot, so that neither bound occurrences of an `x` are substituted,
+// nor free slots of a `t` are captured.
 fn do_term_subst<L: Language, N: Analysis<L>>(
     eg: &mut EGraph<L, N>,
     re: &RecExpr<L>,
-    x: &AppliedId,
-    t: &AppliedId,
+    sigma: &[(AppliedId, AppliedId)],
+    renaming: &SlotMap,
 ) -> AppliedId {
-    let mut n = re.node.clone();
+    let mut n = nullify_app_ids(&re.node);
+    let public: HashSet<Slot> = n.public_slot_occurrences().into_iter().collect();
+    let mut renaming = renaming.clone();
+    for s in n.all_slot_occurrences() {
+        if !public.contains(&s) {
+            renaming.insert(s, Slot::fresh());
+        }
+    }
+    for s in n.all_slot_occurrences_mut() {
+        *s = renaming.get(*s).unwrap_or(*s);
+    }
+
     let mut refs: Vec<&mut AppliedId> = n.applied_id_occurrences_mut();
     if CHECKS {
         assert_eq!(re.children.len(), refs.len());
     }
     for i in 0..refs.len() {
-        *(refs[i]) = do_term_subst(eg, &re.children[i], x, t);
+        *(refs[i]) = do_term_subst(eg, &re.children[i], sigma, &renaming);
     }
     let app_id = eg.add_syn(n);
 
-    if app_id == *x {
-        return t.clone();
-    } else {
-        app_id
+    match sigma.iter().find(|(x, _)| app_id == *x) {
+        Some((_, t)) => t.clone(),
+        None => app_id,
     }
 }
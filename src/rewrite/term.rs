@@ -0,0 +1,486 @@
+use crate::*;
+
+/// The result of matching a [Pattern] against a [RecExpr], see [match_term].
+///
+/// It maps each pattern variable `?x` to a sub-term, and can be used like a `HashMap<String, RecExpr<L>>`.
+#[derive(Clone)]
+pub struct TermSubst<L: Language> {
+    vars: HashMap<String, RecExpr<L>>,
+
+    // the slots of the term, which the slots of the pattern correspond to.
+    slots: HashMap<Slot, Slot>,
+
+    // the operators bound by operator variables, using `AppliedId::null()` as children.
+    ops: HashMap<String, L>,
+}
+
+impl<L: Language> Default for TermSubst<L> {
+    fn default() -> Self {
+        TermSubst {
+            vars: HashMap::default(),
+            slots: HashMap::default(),
+            ops: HashMap::default(),
+        }
+    }
+}
+
+impl<L: Language> std::ops::Deref for TermSubst<L> {
+    type Target = HashMap<String, RecExpr<L>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.vars
+    }
+}
+
+// The lhs, rhs and condition of a [Rewrite], if it is given by patterns.
+// These rules can also be applied to terms, see [Rewrite::apply_term].
+pub(crate) struct TermRule<L: Language> {
+    pub(crate) lhs: Pattern<L>,
+    pub(crate) rhs: Pattern<L>,
+    pub(crate) cond: Option<Condition>,
+}
+
+/// The order in which [rewrite_step] looks for a sub-term to rewrite.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    /// Rewrites the left-most sub-term, none of whose children can be rewritten.
+    Innermost,
+    /// Rewrites the left-most sub-term, which isn't contained in a sub-term that can be rewritten.
+    Outermost,
+}
+
+/// Matches `pat` against the root of `re`, syntactically and modulo alpha-equivalence of binders.
+///
+/// Returns one [TermSubst] per way to match, e.g. for or-patterns.
+pub fn match_term<L: Language>(pat: &Pattern<L>, re: &RecExpr<L>) -> Vec<TermSubst<L>> {
+    match_rec(pat, re, TermSubst::default(), &[])
+}
+
+// `bound` are the slots of `re`, which are bound by the binders of the pattern surrounding `pat`.
+fn match_rec<L: Language>(
+    pat: &Pattern<L>,
+    re: &RecExpr<L>,
+    mut st: TermSubst<L>,
+    bound: &[Slot],
+) -> Vec<TermSubst<L>> {
+    match pat {
+        Pattern::Wildcard => vec![st],
+        Pattern::PVar(v) => bind(st, v, re),
+        Pattern::HOVar(f, args) => {
+            // `?f` may only use those bound slots, which are its parameters.
+            let params: Vec<Slot> = args
+                .iter()
+                .flat_map(HOArg::slots)
+                .filter_map(|s| st.slots.get(&s).copied())
+                .collect();
+            if free_slots(re)
+                .iter()
+                .any(|s| bound.contains(s) && !params.contains(s))
+            {
+                return Vec::new();
+            }
+            bind(st, f, re)
+        }
+        Pattern::Or(alts) => alts
+            .iter()
+            .flat_map(|p| match_rec(p, re, st.clone(), bound))
+            .collect(),
+        Pattern::OpVar(op, children) => {
+            let n = nullify_app_ids(&re.node);
+            if children.len() != re.children.len() {
+                return Vec::new();
+            }
+            if st.ops.get(op).is_some_and(|m| *m != n) {
+                return Vec::new();
+            }
+            st.ops.insert(op.clone(), n);
+            match_children(children, re, st, bound)
+        }
+        Pattern::ENode(n, children) => {
+            if children.len() != re.children.len() {
+                return Vec::new();
+            }
+            let Some(st) = unify_node(n, &re.node, st) else {
+                return Vec::new();
+            };
+            match_children(children, re, st, bound)
+        }
+        // substitutions can't be matched against, see [RewriteError::SubstInLhs].
+        Pattern::Subst(..) => Vec::new(),
+    }
+}
+
+fn match_children<L: Language>(
+    children: &[Pattern<L>],
+    re: &RecExpr<L>,
+    st: TermSubst<L>,
+    bound: &[Slot],
+) -> Vec<TermSubst<L>> {
+    let mut out = vec![st];
+    for (i, (p, c)) in children.iter().zip(&re.children).enumerate() {
+        let mut bound = bound.to_vec();
+        bound.extend(child_binders(&re.node, i));
+        out = out
+            .into_iter()
+            .flat_map(|st| match_rec(p, c, st, &bound))
+            .collect();
+    }
+    out
+}
+
+fn bind<L: Language>(mut st: TermSubst<L>, v: &str, re: &RecExpr<L>) -> Vec<TermSubst<L>> {
+    match st.vars.get(v) {
+        Some(old) if !alpha_eq(old, re) => Vec::new(),
+        Some(_) => vec![st],
+        None => {
+            st.vars.insert(v.to_string(), re.clone());
+            vec![st]
+        }
+    }
+}
+
+// matches the pattern e-node `p` against the term e-node `t`, extending the slot correspondence of `st`.
+fn unify_node<L: Language>(p: &L, t: &L, mut st: TermSubst<L>) -> Option<TermSubst<L>> {
+    let p = nullify_app_ids(p).to_syntax();
+    let t = nullify_app_ids(t).to_syntax();
+    if p.len() != t.len() {
+        return None;
+    }
+    for (x, y) in p.iter().zip(&t) {
+        match (x, y) {
+            (SyntaxElem::String(x), SyntaxElem::String(y)) if x == y => {}
+            (SyntaxElem::AppliedId(_), SyntaxElem::AppliedId(_)) => {}
+            (SyntaxElem::Slot(x), SyntaxElem::Slot(y)) => match st.slots.get(x) {
+                Some(z) if z == y => {}
+                Some(_) => return None,
+                // the correspondence has to be a bijection.
+                None if st.slots.values().any(|z| z == y) => return None,
+                None => {
+                    st.slots.insert(*x, *y);
+                }
+            },
+            _ => return None,
+        }
+    }
+    Some(st)
+}
+
+/// Whether `a` and `b` are equal, up to renaming their bound slots.
+pub fn alpha_eq<L: Language>(a: &RecExpr<L>, b: &RecExpr<L>) -> bool {
+    alpha_eq_rec(a, b, &[])
+}
+
+// `env` pairs the binders of `a` with the binders of `b`, which are in scope.
+fn alpha_eq_rec<L: Language>(a: &RecExpr<L>, b: &RecExpr<L>, env: &[(Slot, Slot)]) -> bool {
+    let sa = nullify_app_ids(&a.node).to_syntax();
+    let sb = nullify_app_ids(&b.node).to_syntax();
+    if sa.len() != sb.len() || a.children.len() != b.children.len() {
+        return false;
+    }
+    let (pa, pb) = (private_slots(&a.node), private_slots(&b.node));
+    let mut binders: Vec<(Slot, Slot)> = Vec::new();
+    for (x, y) in sa.iter().zip(&sb) {
+        let ok = match (x, y) {
+            (SyntaxElem::String(x), SyntaxElem::String(y)) => x == y,
+            (SyntaxElem::AppliedId(_), SyntaxElem::AppliedId(_)) => true,
+            (SyntaxElem::Slot(x), SyntaxElem::Slot(y)) if pa.contains(x) && pb.contains(y) => {
+                match binders.iter().find(|(x2, y2)| x2 == x || y2 == y) {
+                    Some(pair) => *pair == (*x, *y),
+                    None => {
+                        binders.push((*x, *y));
+                        true
+                    }
+                }
+            }
+            (SyntaxElem::Slot(x), SyntaxElem::Slot(y)) if !pa.contains(x) && !pb.contains(y) => {
+                // the innermost binder of `x` has to be paired with the innermost binder of `y`.
+                match env.iter().rev().find(|(x2, y2)| x2 == x || y2 == y) {
+                    Some(pair) => *pair == (*x, *y),
+                    None => x == y,
+                }
+            }
+            _ => false,
+        };
+        if !ok {
+            return false;
+        }
+    }
+    a.children
+        .iter()
+        .zip(&b.children)
+        .enumerate()
+        .all(|(i, (ca, cb))| {
+            let scope = child_binders(&a.node, i);
+            let mut env = env.to_vec();
+            env.extend(binders.iter().filter(|(x, _)| scope.contains(x)));
+            alpha_eq_rec(ca, cb, &env)
+        })
+}
+
+// the slots occurring free in `re`.
+fn free_slots<L: Language>(re: &RecExpr<L>) -> HashSet<Slot> {
+    let mut out: HashSet<Slot> = nullify_app_ids(&re.node)
+        .public_slot_occurrences()
+        .into_iter()
+        .collect();
+    for (i, c) in re.children.iter().enumerate() {
+        let scope = child_binders(&re.node, i);
+        out.extend(free_slots(c).into_iter().filter(|s| !scope.contains(s)));
+    }
+    out
+}
+
+// Renames the binders of `re` which shadow another binder, or which clash with a free slot.
+// Afterwards, each binder uses a different slot, so rules can move sub-terms around without capturing anything.
+fn distinct_binders<L: Language>(re: &RecExpr<L>) -> RecExpr<L> {
+    fn rec<L: Language>(
+        re: &RecExpr<L>,
+        env: &HashMap<Slot, Slot>,
+        used: &mut HashSet<Slot>,
+    ) -> RecExpr<L> {
+        let mut renamed = HashMap::default();
+        for s in private_slots(&re.node) {
+            let t = if used.contains(&s) { Slot::fresh() } else { s };
+            used.insert(t);
+            renamed.insert(s, t);
+        }
+        let mut node = nullify_app_ids(&re.node);
+        for s in node.all_slot_occurrences_mut() {
+            *s = *renamed.get(s).or(env.get(s)).unwrap_or(s);
+        }
+        let children = re
+            .children
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let mut env = env.clone();
+                for s in child_binders(&re.node, i) {
+                    env.insert(s, renamed[&s]);
+                }
+                rec(c, &env, used)
+            })
+            .collect();
+        RecExpr { node, children }
+    }
+    rec(re, &HashMap::default(), &mut free_slots(re))
+}
+
+// renames the free slots of `re`.
+fn rename_free<L: Language>(re: &RecExpr<L>, m: &HashMap<Slot, Slot>) -> RecExpr<L> {
+    let private = private_slots(&re.node);
+    let mut node = nullify_app_ids(&re.node);
+    for s in node.all_slot_occurrences_mut() {
+        if !private.contains(s) {
+            *s = *m.get(s).unwrap_or(s);
+        }
+    }
+    let children = re
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let mut m = m.clone();
+            for s in child_binders(&re.node, i) {
+                m.remove(&s);
+            }
+            rename_free(c, &m)
+        })
+        .collect();
+    RecExpr { node, children }
+}
+
+// computes the simultaneous substitution `b[x := t, ...]`.
+// As the binders of the term are distinct, no slot of `t` can be captured.
+fn subst_term<L: Language>(b: &RecExpr<L>, sigma: &[(RecExpr<L>, RecExpr<L>)]) -> RecExpr<L> {
+    if let Some((_, t)) = sigma.iter().find(|(x, _)| alpha_eq(x, b)) {
+        return t.clone();
+    }
+    let children = b
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            // a binder hides the outer meaning of `x`.
+            let scope = child_binders(&b.node, i);
+            let sigma: Vec<_> = sigma
+                .iter()
+                .filter(|(x, _)| free_slots(x).iter().all(|s| !scope.contains(s)))
+                .cloned()
+                .collect();
+            subst_term(c, &sigma)
+        })
+        .collect();
+    RecExpr {
+        node: b.node.clone(),
+        children,
+    }
+}
+
+// instantiates the rhs `pat` of a rule for the match `st`.
+// Slots of `pat`, which don't correspond to a slot of the matched term, are chosen fresh.
+fn instantiate<L: Language>(
+    pat: &Pattern<L>,
+    st: &mut TermSubst<L>,
+    params: &HashMap<String, Vec<HOArg<L>>>,
+) -> RecExpr<L> {
+    match pat {
+        Pattern::ENode(n, children) => {
+            let mut node = nullify_app_ids(n);
+            for s in node.all_slot_occurrences_mut() {
+                *s = *st.slots.entry(*s).or_insert_with(Slot::fresh);
+            }
+            let children = children
+                .iter()
+                .map(|c| instantiate(c, st, params))
+                .collect();
+            RecExpr { node, children }
+        }
+        Pattern::PVar(v) => st
+            .vars
+            .get(v)
+            .unwrap_or_else(|| {
+                panic!("encountered `?{v}` in pattern, but it is missing in the `subst`")
+            })
+            .clone(),
+        Pattern::Subst(b, sigma) => {
+            let b = instantiate(b, st, params);
+            let sigma: Vec<_> = sigma
+                .iter()
+                .map(|(x, t)| (instantiate(x, st, params), instantiate(t, st, params)))
+                .collect();
+            subst_term(&b, &sigma)
+        }
+        Pattern::OpVar(op, children) => {
+            let Some(node) = st.ops.get(op).cloned() else {
+                panic!("encountered `?{op}` in pattern, but it is missing in the `subst`")
+            };
+            let children = children
+                .iter()
+                .map(|c| instantiate(c, st, params))
+                .collect();
+            RecExpr { node, children }
+        }
+        Pattern::Wildcard | Pattern::Or(_) => {
+            panic!(
+                "Can't instantiate `{pat}`, wildcards and or-patterns are only allowed on the lhs"
+            )
+        }
+        Pattern::HOVar(f, args) => {
+            let b = instantiate(&Pattern::PVar(f.clone()), st, params);
+            let Some(ps) = params.get(f) else {
+                panic!("`?{f}[..]` needs to be bound by a higher-order pattern variable on the lhs")
+            };
+            assert_eq!(
+                ps.len(),
+                args.len(),
+                "`?{f}[..]` is instantiated with the wrong number of arguments"
+            );
+            let mut renaming = HashMap::default();
+            let mut sigma = Vec::new();
+            for (p, a) in ps.iter().zip(args) {
+                match (p, a) {
+                    (HOArg::Slot(p), HOArg::Slot(a)) => {
+                        let p = *st.slots.entry(*p).or_insert_with(Slot::fresh);
+                        let a = *st.slots.entry(*a).or_insert_with(Slot::fresh);
+                        renaming.insert(p, a);
+                    }
+                    (HOArg::Term(p), HOArg::Term(a)) => {
+                        let x = instantiate(p, st, params);
+                        let t = instantiate(a, st, params);
+                        sigma.push((x, t));
+                    }
+                    _ => panic!("slot parameters can only be instantiated by slots, and term parameters by terms"),
+                }
+            }
+            rename_free(&subst_term(&b, &sigma), &renaming)
+        }
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Rewrite<L, N> {
+    /// Applies this rule to the root of `re`, using the first match which satisfies the conditions of the rule.
+    ///
+    /// Returns `None` if the rule doesn't match, or if it can't be applied to terms.
+    /// Only rules given by patterns can be applied to terms, i.e. rules created by [Rewrite::new] or [parse_rules],
+    /// but not rules with closure conditions or custom [Applier]s.
+    pub fn apply_term(&self, re: &RecExpr<L>) -> Option<RecExpr<L>> {
+        self.apply_term_distinct(&distinct_binders(re))
+    }
+
+    // like `apply_term`, for terms whose binders were already made distinct by `distinct_binders`.
+    fn apply_term_distinct(&self, re: &RecExpr<L>) -> Option<RecExpr<L>> {
+        let rule = self.term_rule.as_ref()?;
+        let params = ho_params(&rule.lhs);
+        let mut st = match_term(&rule.lhs, re)
+            .into_iter()
+            .find(|st| rule.cond.as_ref().is_none_or(|c| check_term(c, st)))?;
+        Some(instantiate(&rule.rhs, &mut st, &params))
+    }
+}
+
+// evaluates the condition `c` for the match `st`.
+fn check_term<L: Language>(c: &Condition, st: &TermSubst<L>) -> bool {
+    match c {
+        Condition::Fresh(s, v) => {
+            let Some(x) = st.vars.get(v) else {
+                panic!("condition `{c}` refers to `?{v}`, but it is missing in the `subst`")
+            };
+            st.slots.get(s).is_none_or(|s| !free_slots(x).contains(s))
+        }
+        Condition::Not(c) => !check_term(c, st),
+        Condition::And(cs) => cs.iter().all(|c| check_term(c, st)),
+        Condition::Or(cs) => cs.iter().any(|c| check_term(c, st)),
+    }
+}
+
+/// Applies the first applicable rule (see [Rewrite::apply_term]) to a single sub-term of `re`, chosen by the `strategy`.
+///
+/// Returns `None` if no rule can be applied to any sub-term, i.e. if `re` is in normal form.
+pub fn rewrite_step<L: Language, N: Analysis<L>>(
+    re: &RecExpr<L>,
+    rules: &[Rewrite<L, N>],
+    strategy: Strategy,
+) -> Option<RecExpr<L>> {
+    step_rec(&distinct_binders(re), rules, strategy)
+}
+
+fn step_rec<L: Language, N: Analysis<L>>(
+    re: &RecExpr<L>,
+    rules: &[Rewrite<L, N>],
+    strategy: Strategy,
+) -> Option<RecExpr<L>> {
+    let at_root = || rules.iter().find_map(|rw| rw.apply_term_distinct(re));
+    if strategy == Strategy::Outermost {
+        if let Some(out) = at_root() {
+            return Some(out);
+        }
+    }
+    for (i, c) in re.children.iter().enumerate() {
+        if let Some(c) = step_rec(c, rules, strategy) {
+            let mut out = re.clone();
+            out.children[i] = c;
+            return Some(out);
+        }
+    }
+    match strategy {
+        Strategy::Innermost => at_root(),
+        Strategy::Outermost => None,
+    }
+}
+
+/// Rewrites `re` with [rewrite_step] until it is in normal form, or until `max_steps` steps were made.
+pub fn normalize<L: Language, N: Analysis<L>>(
+    re: &RecExpr<L>,
+    rules: &[Rewrite<L, N>],
+    strategy: Strategy,
+    max_steps: usize,
+) -> RecExpr<L> {
+    let mut re = re.clone();
+    for _ in 0..max_steps {
+        match rewrite_step(&re, rules, strategy) {
+            Some(x) => re = x,
+            None => break,
+        }
+    }
+    re
+}
@@ -0,0 +1,555 @@
+use crate::*;
+
+/// A most general unifier of two patterns, see [unify].
+///
+/// It instantiates pattern variables with patterns, and renames slots.
+/// It only holds under its freshness constraints: `($x, "v")` requires that `$x` doesn't occur free in the instantiation of `?v`.
+#[derive(Clone, Debug)]
+pub struct Unifier<L: Language> {
+    /// The instantiations of the pattern variables.
+    pub vars: HashMap<String, Pattern<L>>,
+    /// The renaming of the slots. Slots which aren't renamed are left out.
+    pub slots: HashMap<Slot, Slot>,
+    /// The freshness constraints `$x # ?v`, for pattern variables which aren't instantiated.
+    pub fresh: Vec<(Slot, String)>,
+}
+
+/// An overlap of two rules, see [critical_pairs].
+#[derive(Clone, Debug)]
+pub struct CriticalPair<L: Language> {
+    /// The term, to which the first rule applies at the root, and the second rule at `position`.
+    pub overlap: Pattern<L>,
+    /// The path of child indices from the root of `overlap` to where the second rule applies.
+    pub position: Vec<usize>,
+    /// The result of applying the first rule.
+    pub left: Pattern<L>,
+    /// The result of applying the second rule.
+    pub right: Pattern<L>,
+    /// The freshness constraints, under which the overlap exists.
+    pub fresh: Vec<(Slot, String)>,
+}
+
+/// Unifies `a` and `b` modulo alpha-equivalence, i.e. nominal unification.
+///
+/// Pattern variables are instantiated by patterns, and slots are renamed injectively, i.e. the distinct slots of `a` stay distinct, and so do those of `b`.
+/// A slot with the same name in `a` and `b` is the same slot; and each binder should use a slot which occurs nowhere else.
+/// A higher-order pattern variable `?f[..]` is treated like `?f`, with the freshness constraints expressed by its parameters:
+/// `?f` may not use the surrounding binders, except its parameters.
+///
+/// Returns `None` if the patterns don't unify,
+/// or if a pattern contains anything other than e-nodes and (higher-order) pattern variables,
+/// e.g. wildcards, or-patterns, operator variables or substitutions.
+pub fn unify<L: Language>(a: &Pattern<L>, b: &Pattern<L>) -> Option<Unifier<L>> {
+    let mut fresh = Vec::new();
+    let a = first_order(a, &mut Vec::new(), &mut fresh)?;
+    let b = first_order(b, &mut Vec::new(), &mut fresh)?;
+    unify_first_order(&a, &b, &fresh, [&a, &b])
+}
+
+/// Enumerates the critical pairs of the rules `r1` and `r2`, i.e. the most general terms to which `r1` applies at the root, and `r2` at a non-variable position.
+///
+/// If the rules are confluent, both results `left` and `right` of each critical pair can be rewritten to the same term.
+/// For this, the second rule is renamed apart: its pattern variables and slots get a `'` appended.
+/// The freshness conditions `$x # ?v` of the rules are taken into account, any other conditions are ignored.
+///
+/// Only rules constructed from patterns are supported, see [Rewrite::apply_term].
+/// Rules whose lhs contains wildcards, or-patterns or operator variables have no critical pairs.
+/// Neither have overlaps whose results can't be written as patterns,
+/// as that would require to rename a slot within an uninstantiated pattern variable.
+pub fn critical_pairs<L: Language, N: Analysis<L>>(
+    r1: &Rewrite<L, N>,
+    r2: &Rewrite<L, N>,
+) -> Vec<CriticalPair<L>> {
+    let (Some(t1), Some(t2)) = (&r1.term_rule, &r2.term_rule) else {
+        return Vec::new();
+    };
+    let t2 = rename_apart(t2, t1);
+
+    let mut fresh = Vec::new();
+    let (Some(l1), Some(l2)) = (
+        first_order(&t1.lhs, &mut Vec::new(), &mut fresh),
+        first_order(&t2.lhs, &mut Vec::new(), &mut fresh),
+    ) else {
+        return Vec::new();
+    };
+    for c in t1.cond.iter().chain(&t2.cond) {
+        fresh_conditions(c, &mut fresh);
+    }
+    let (params1, params2) = (ho_params(&t1.lhs), ho_params(&t2.lhs));
+
+    let mut positions = Vec::new();
+    subpatterns(&l1, &mut Vec::new(), &mut positions);
+
+    let mut out = Vec::new();
+    for (position, sub) in positions {
+        // a rule trivially overlaps with itself at the root.
+        if matches!(sub, Pattern::PVar(_)) || (position.is_empty() && std::ptr::eq(r1, r2)) {
+            continue;
+        }
+        let Some(u) = unify_first_order(sub, &l2, &fresh, [&l1, &l2]) else {
+            continue;
+        };
+        let overlap = u.apply(&l1);
+        let (Some(left), Some(inner)) = (
+            u.instantiate(&t1.rhs, &params1),
+            u.instantiate(&t2.rhs, &params2),
+        ) else {
+            continue;
+        };
+        let right = replace_at(&overlap, &position, inner);
+        out.push(CriticalPair {
+            overlap,
+            position,
+            left,
+            right,
+            fresh: u.fresh,
+        });
+    }
+    out
+}
+
+impl<L: Language> Unifier<L> {
+    /// Applies the unifier to `pat`, i.e. instantiates its pattern variables and renames its slots.
+    pub fn apply(&self, pat: &Pattern<L>) -> Pattern<L> {
+        match pat {
+            Pattern::ENode(n, children) => Pattern::ENode(
+                self.rename_node(n),
+                children.iter().map(|c| self.apply(c)).collect(),
+            ),
+            Pattern::PVar(v) => self.vars.get(v).cloned().unwrap_or_else(|| pat.clone()),
+            Pattern::Subst(b, sigma) => Pattern::Subst(
+                Box::new(self.apply(b)),
+                sigma
+                    .iter()
+                    .map(|(x, t)| (self.apply(x), self.apply(t)))
+                    .collect(),
+            ),
+            Pattern::HOVar(f, args) => match self.vars.get(f) {
+                Some(t) => t.clone(),
+                None => Pattern::HOVar(f.clone(), self.apply_args(args)),
+            },
+            Pattern::Wildcard => Pattern::Wildcard,
+            Pattern::Or(alts) => Pattern::Or(alts.iter().map(|c| self.apply(c)).collect()),
+            Pattern::OpVar(op, children) => {
+                Pattern::OpVar(op.clone(), children.iter().map(|c| self.apply(c)).collect())
+            }
+        }
+    }
+
+    // applies the unifier to the rhs `pat`, whose higher-order pattern variables have the parameters `params` on the lhs.
+    // Returns `None`, if a slot parameter would have to be renamed within a pattern variable.
+    fn instantiate(
+        &self,
+        pat: &Pattern<L>,
+        params: &HashMap<String, Vec<HOArg<L>>>,
+    ) -> Option<Pattern<L>> {
+        Some(match pat {
+            Pattern::ENode(n, children) => Pattern::ENode(
+                self.rename_node(n),
+                children
+                    .iter()
+                    .map(|c| self.instantiate(c, params))
+                    .collect::<Option<_>>()?,
+            ),
+            Pattern::Subst(b, sigma) => Pattern::Subst(
+                Box::new(self.instantiate(b, params)?),
+                sigma
+                    .iter()
+                    .map(|(x, t)| {
+                        Some((self.instantiate(x, params)?, self.instantiate(t, params)?))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Pattern::HOVar(f, args) => {
+                let args = self.apply_args(args);
+                let Some(t) = self.vars.get(f) else {
+                    return Some(Pattern::HOVar(f.clone(), args));
+                };
+                let ps = params.get(f).map(|ps| self.apply_args(ps));
+                let ps = ps.unwrap_or_else(|| args.clone());
+
+                // instantiate the parameters of `?f` with the arguments.
+                let mut renaming = HashMap::default();
+                let mut sigma = Vec::new();
+                for (p, a) in ps.into_iter().zip(args) {
+                    match (p, a) {
+                        (HOArg::Slot(p), HOArg::Slot(a)) if p != a => {
+                            renaming.insert(p, a);
+                        }
+                        (HOArg::Term(p), HOArg::Term(a)) if p != a => sigma.push((p, a)),
+                        _ => {}
+                    }
+                }
+                let t = self.rename_free(t, &renaming)?;
+                match sigma.is_empty() {
+                    true => t,
+                    false => Pattern::Subst(Box::new(t), sigma),
+                }
+            }
+            _ => self.apply(pat),
+        })
+    }
+
+    fn apply_args(&self, args: &[HOArg<L>]) -> Vec<HOArg<L>> {
+        args.iter()
+            .map(|a| match a {
+                HOArg::Slot(s) => HOArg::Slot(*self.slots.get(s).unwrap_or(s)),
+                HOArg::Term(t) => HOArg::Term(self.apply(t)),
+            })
+            .collect()
+    }
+
+    fn rename_node(&self, n: &L) -> L {
+        let mut n = n.clone();
+        for s in n.all_slot_occurrences_mut() {
+            *s = *self.slots.get(s).unwrap_or(s);
+        }
+        n
+    }
+
+    // renames the free slots of `pat`.
+    // Returns `None` if a renamed slot may occur in a pattern variable, i.e. there is no freshness constraint against it.
+    fn rename_free(&self, pat: &Pattern<L>, m: &HashMap<Slot, Slot>) -> Option<Pattern<L>> {
+        match pat {
+            Pattern::ENode(n, children) => {
+                let private = private_slots(n);
+                let mut n = n.clone();
+                for s in n.all_slot_occurrences_mut() {
+                    if !private.contains(s) {
+                        *s = *m.get(s).unwrap_or(s);
+                    }
+                }
+                let children = children
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let mut m = m.clone();
+                        for s in child_binders(&n, i) {
+                            m.remove(&s);
+                        }
+                        self.rename_free(c, &m)
+                    })
+                    .collect::<Option<_>>()?;
+                Some(Pattern::ENode(n, children))
+            }
+            Pattern::PVar(v) => m
+                .keys()
+                .all(|s| self.fresh.contains(&(*s, v.clone())))
+                .then(|| pat.clone()),
+            _ => None,
+        }
+    }
+}
+
+// The state of the unification: the instantiated pattern variables, and a union-find of the slots.
+struct State<L: Language> {
+    vars: HashMap<String, Pattern<L>>,
+    parent: HashMap<Slot, Slot>,
+}
+
+impl<L: Language> State<L> {
+    fn find(&self, mut s: Slot) -> Slot {
+        while let Some(t) = self.parent.get(&s) {
+            s = *t;
+        }
+        s
+    }
+
+    // the representative of the merged class is the one of `a`.
+    fn union(&mut self, a: Slot, b: Slot) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent.insert(b, a);
+        }
+    }
+
+    // follows the instantiations of pattern variables at the root of `pat`.
+    fn walk<'a>(&'a self, mut pat: &'a Pattern<L>) -> &'a Pattern<L> {
+        while let Pattern::PVar(v) = pat {
+            match self.vars.get(v) {
+                Some(t) => pat = t,
+                None => break,
+            }
+        }
+        pat
+    }
+
+    fn occurs(&self, v: &str, pat: &Pattern<L>) -> bool {
+        match pat {
+            Pattern::ENode(_, children) => children.iter().any(|c| self.occurs(v, c)),
+            Pattern::PVar(w) => w == v || self.vars.get(w).is_some_and(|t| self.occurs(v, t)),
+            _ => false,
+        }
+    }
+
+    fn solve(&mut self, a: &Pattern<L>, b: &Pattern<L>) -> bool {
+        let mut todo = vec![(a.clone(), b.clone())];
+        while let Some((a, b)) = todo.pop() {
+            let a = self.walk(&a).clone();
+            let b = self.walk(&b).clone();
+            match (&a, &b) {
+                (Pattern::PVar(x), Pattern::PVar(y)) if x == y => {}
+                (Pattern::PVar(x), t) | (t, Pattern::PVar(x)) => {
+                    if self.occurs(x, t) {
+                        return false;
+                    }
+                    self.vars.insert(x.clone(), t.clone());
+                }
+                (Pattern::ENode(n, cn), Pattern::ENode(m, cm)) => {
+                    let sn = nullify_app_ids(n).to_syntax();
+                    let sm = nullify_app_ids(m).to_syntax();
+                    if sn.len() != sm.len() || cn.len() != cm.len() {
+                        return false;
+                    }
+                    for (x, y) in sn.into_iter().zip(sm) {
+                        match (x, y) {
+                            (SyntaxElem::Slot(x), SyntaxElem::Slot(y)) => self.union(x, y),
+                            (SyntaxElem::String(x), SyntaxElem::String(y)) if x == y => {}
+                            (SyntaxElem::AppliedId(_), SyntaxElem::AppliedId(_)) => {}
+                            _ => return false,
+                        }
+                    }
+                    todo.extend(cn.iter().cloned().zip(cm.iter().cloned()));
+                }
+                _ => unreachable!(),
+            }
+        }
+        true
+    }
+
+    // instantiates the pattern variables of `pat` and renames its slots to their representatives.
+    fn resolve(&self, pat: &Pattern<L>) -> Pattern<L> {
+        match pat {
+            Pattern::ENode(n, children) => {
+                let mut n = n.clone();
+                for s in n.all_slot_occurrences_mut() {
+                    *s = self.find(*s);
+                }
+                Pattern::ENode(n, children.iter().map(|c| self.resolve(c)).collect())
+            }
+            Pattern::PVar(v) => match self.vars.get(v) {
+                Some(t) => self.resolve(t),
+                None => pat.clone(),
+            },
+            _ => pat.clone(),
+        }
+    }
+
+    // checks `s # pat` for a resolved `pat`, and collects the remaining constraints on pattern variables.
+    fn check_fresh(&self, s: Slot, pat: &Pattern<L>, out: &mut Vec<(Slot, String)>) -> bool {
+        match pat {
+            Pattern::ENode(n, children) => {
+                !nullify_app_ids(n).public_slot_occurrences().contains(&s)
+                    && children.iter().enumerate().all(|(i, c)| {
+                        child_binders(n, i).contains(&s) || self.check_fresh(s, c, out)
+                    })
+            }
+            Pattern::PVar(v) => {
+                if !out.contains(&(s, v.clone())) {
+                    out.push((s, v.clone()));
+                }
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+// unifies the first-order patterns `a` and `b` under the constraints `fresh`.
+// The slots of each of the patterns `sides` have to stay distinct.
+fn unify_first_order<L: Language>(
+    a: &Pattern<L>,
+    b: &Pattern<L>,
+    fresh: &[(Slot, String)],
+    sides: [&Pattern<L>; 2],
+) -> Option<Unifier<L>> {
+    let mut st = State {
+        vars: HashMap::default(),
+        parent: HashMap::default(),
+    };
+    if !st.solve(a, b) {
+        return None;
+    }
+
+    let mut slots = HashMap::default();
+    for side in sides {
+        let mut seen: HashMap<Slot, Slot> = HashMap::default();
+        for s in pattern_slots(side) {
+            let r = st.find(s);
+            if *seen.entry(r).or_insert(s) != s {
+                return None;
+            }
+            if r != s {
+                slots.insert(s, r);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for (s, v) in fresh {
+        let t = st.resolve(&Pattern::PVar(v.clone()));
+        if !st.check_fresh(st.find(*s), &t, &mut out) {
+            return None;
+        }
+    }
+    let vars = st
+        .vars
+        .keys()
+        .map(|v| (v.clone(), st.resolve(&Pattern::PVar(v.clone()))))
+        .collect();
+    Some(Unifier {
+        vars,
+        slots,
+        fresh: out,
+    })
+}
+
+// replaces the higher-order pattern variables `?f[..]` of the lhs `pat` by `?f`, and collects the freshness constraints they express.
+// Returns `None` if `pat` contains anything other than e-nodes and pattern variables.
+fn first_order<L: Language>(
+    pat: &Pattern<L>,
+    scope: &mut Vec<Slot>,
+    fresh: &mut Vec<(Slot, String)>,
+) -> Option<Pattern<L>> {
+    match pat {
+        Pattern::ENode(n, children) => {
+            let mut out = Vec::new();
+            for (i, c) in children.iter().enumerate() {
+                let k = scope.len();
+                scope.extend(child_binders(n, i));
+                out.push(first_order(c, scope, fresh));
+                scope.truncate(k);
+            }
+            Some(Pattern::ENode(
+                n.clone(),
+                out.into_iter().collect::<Option<_>>()?,
+            ))
+        }
+        Pattern::PVar(_) => Some(pat.clone()),
+        Pattern::HOVar(f, args) => {
+            let params: Vec<Slot> = args.iter().flat_map(HOArg::slots).collect();
+            for s in scope.iter() {
+                if !params.contains(s) && !fresh.contains(&(*s, f.clone())) {
+                    fresh.push((*s, f.clone()));
+                }
+            }
+            Some(Pattern::PVar(f.clone()))
+        }
+        _ => None,
+    }
+}
+
+// the freshness constraints `$x # ?v` implied by the condition `c`.
+fn fresh_conditions(c: &Condition, out: &mut Vec<(Slot, String)>) {
+    match c {
+        Condition::Fresh(s, v) => out.push((*s, v.clone())),
+        Condition::And(cs) => cs.iter().for_each(|c| fresh_conditions(c, out)),
+        Condition::Not(_) | Condition::Or(_) => {}
+    }
+}
+
+// renames the pattern variables and slots of `t`, such that they differ from those of `other`.
+fn rename_apart<L: Language>(t: &TermRule<L>, other: &TermRule<L>) -> TermRule<L> {
+    let mut taken_vars = Vec::new();
+    rhs_pvars(&other.lhs, &mut taken_vars);
+    rhs_pvars(&other.rhs, &mut taken_vars);
+    let mut taken_slots = pattern_slots(&other.lhs);
+    taken_slots.extend(pattern_slots(&other.rhs));
+
+    let var = |v: &str| {
+        let mut v = format!("{v}'");
+        while taken_vars.contains(&v) {
+            v.push('\'');
+        }
+        v
+    };
+    let slot = |s: Slot| {
+        let mut name = format!("{}'", &s.to_string()[1..]);
+        while taken_slots.contains(&Slot::named(&name)) {
+            name.push('\'');
+        }
+        Slot::named(&name)
+    };
+    TermRule {
+        lhs: rename_pattern(&t.lhs, &var, &slot),
+        rhs: rename_pattern(&t.rhs, &var, &slot),
+        cond: t.cond.as_ref().map(|c| rename_condition(c, &var, &slot)),
+    }
+}
+
+fn rename_pattern<L: Language>(
+    pat: &Pattern<L>,
+    var: &impl Fn(&str) -> String,
+    slot: &impl Fn(Slot) -> Slot,
+) -> Pattern<L> {
+    let rec = |p: &Pattern<L>| rename_pattern(p, var, slot);
+    match pat {
+        Pattern::ENode(n, children) => {
+            let mut n = n.clone();
+            for s in n.all_slot_occurrences_mut() {
+                *s = slot(*s);
+            }
+            Pattern::ENode(n, children.iter().map(rec).collect())
+        }
+        Pattern::PVar(v) => Pattern::PVar(var(v)),
+        Pattern::Subst(b, sigma) => Pattern::Subst(
+            Box::new(rec(b)),
+            sigma.iter().map(|(x, t)| (rec(x), rec(t))).collect(),
+        ),
+        Pattern::HOVar(f, args) => {
+            let args = args
+                .iter()
+                .map(|a| match a {
+                    HOArg::Slot(s) => HOArg::Slot(slot(*s)),
+                    HOArg::Term(t) => HOArg::Term(rec(t)),
+                })
+                .collect();
+            Pattern::HOVar(var(f), args)
+        }
+        Pattern::Wildcard => Pattern::Wildcard,
+        Pattern::Or(alts) => Pattern::Or(alts.iter().map(rec).collect()),
+        Pattern::OpVar(op, children) => Pattern::OpVar(var(op), children.iter().map(rec).collect()),
+    }
+}
+
+fn rename_condition(
+    c: &Condition,
+    var: &impl Fn(&str) -> String,
+    slot: &impl Fn(Slot) -> Slot,
+) -> Condition {
+    let rec = |c: &Condition| rename_condition(c, var, slot);
+    match c {
+        Condition::Fresh(s, v) => Condition::Fresh(slot(*s), var(v)),
+        Condition::Not(c) => Condition::Not(Box::new(rec(c))),
+        Condition::And(cs) => Condition::And(cs.iter().map(rec).collect()),
+        Condition::Or(cs) => Condition::Or(cs.iter().map(rec).collect()),
+    }
+}
+
+// the sub-patterns of the first-order `pat`, together with their positions.
+fn subpatterns<'a, L: Language>(
+    pat: &'a Pattern<L>,
+    position: &mut Vec<usize>,
+    out: &mut Vec<(Vec<usize>, &'a Pattern<L>)>,
+) {
+    out.push((position.clone(), pat));
+    if let Pattern::ENode(_, children) = pat {
+        for (i, c) in children.iter().enumerate() {
+            position.push(i);
+            subpatterns(c, position, out);
+            position.pop();
+        }
+    }
+}
+
+fn replace_at<L: Language>(pat: &Pattern<L>, position: &[usize], new: Pattern<L>) -> Pattern<L> {
+    let Some((i, rest)) = position.split_first() else {
+        return new;
+    };
+    let Pattern::ENode(n, children) = pat else {
+        unreachable!()
+    };
+    let mut children = children.clone();
+    children[*i] = replace_at(&children[*i], rest, new);
+    Pattern::ENode(n.clone(), children)
+}
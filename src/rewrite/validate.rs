@@ -0,0 +1,394 @@
+use crate::*;
+
+/// A problem with a rewrite rule, detected by [Rewrite::try_new], [Rewrite::try_new_multi], [Rewrite::try_from_parts] or [explicit_subst_rules].
+#[derive(Debug)]
+pub enum RewriteError {
+    Parse(ParseError),
+    /// The lhs contains a substitution `b[x := t]`, which can't be matched against.
+    SubstInLhs(String),
+    /// Wildcards `_` and or-patterns `(| ..)` can't be instantiated on the rhs.
+    UnsupportedInRhs(String),
+    /// The pattern variable is used on the rhs or in a condition, but not bound by (every alternative of) the lhs.
+    UnboundPVar(String),
+    /// `?f[..]` is used on the rhs, but `?f` is not a higher-order pattern variable on the lhs.
+    NotHigherOrder(String),
+    /// The slot occurs free on the rhs or in a condition, but doesn't occur on the lhs.
+    UnboundSlot(Slot),
+    /// The slot is used both as a binder and as a free slot in the same pattern.
+    BoundAndFree(Slot),
+    /// The slot is bound by a binder within the scope of another binder of the same slot.
+    BoundTwice(Slot),
+    /// `?f[..]` is used on the rhs with a different number of arguments than `?f` has parameters on the lhs.
+    HOArity(String),
+    /// `?f[..]` is used on the rhs with a term argument for a slot parameter, or a slot argument for a term parameter.
+    HOArgKind(String),
+    /// The operator variable `?op` is used with different numbers of children.
+    OpArity(String),
+    /// The applier needs the pattern of the searcher (see [Applier::needs_lhs]), but the searcher doesn't have a single one.
+    MissingLhs,
+    /// The variable node given to [explicit_subst_rules] isn't an e-node with the single slot `$1`.
+    InvalidVarNode(String),
+    /// The let node given to [explicit_subst_rules] doesn't bind `$1` in `?b` or doesn't have the children `?b` and `?t`.
+    InvalidLetNode(String),
+}
+
+impl std::fmt::Display for RewriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RewriteError::Parse(e) => write!(f, "parse error: {e:?}"),
+            RewriteError::SubstInLhs(p) => {
+                write!(
+                    f,
+                    "the lhs contains the substitution `{p}`, which can't be matched against"
+                )
+            }
+            RewriteError::UnsupportedInRhs(p) => {
+                write!(f, "`{p}` can't be instantiated on the rhs")
+            }
+            RewriteError::UnboundPVar(v) => write!(f, "`?{v}` is not bound by the lhs"),
+            RewriteError::NotHigherOrder(v) => {
+                write!(
+                    f,
+                    "`?{v}[..]` is used, but `?{v}` is not higher-order on the lhs"
+                )
+            }
+            RewriteError::UnboundSlot(s) => write!(f, "the slot `{s}` doesn't occur on the lhs"),
+            RewriteError::BoundAndFree(s) => {
+                write!(f, "the slot `{s}` is used both bound and free")
+            }
+            RewriteError::BoundTwice(s) => {
+                write!(f, "the slot `{s}` is bound within its own scope")
+            }
+            RewriteError::HOArity(p) => {
+                write!(
+                    f,
+                    "`{p}` has a different number of arguments than on the lhs"
+                )
+            }
+            RewriteError::HOArgKind(p) => {
+                write!(
+                    f,
+                    "`{p}` has a slot argument for a term parameter, or vice versa"
+                )
+            }
+            RewriteError::OpArity(v) => {
+                write!(f, "`?{v}` is used with different numbers of children")
+            }
+            RewriteError::MissingLhs => {
+                write!(f, "this applier requires a searcher with a single pattern")
+            }
+            RewriteError::InvalidVarNode(p) => {
+                write!(
+                    f,
+                    "the variable node `{p}` needs to be an e-node with the single slot `$1`"
+                )
+            }
+            RewriteError::InvalidLetNode(p) => {
+                write!(
+                    f,
+                    "the let node `{p}` needs to bind the slot `$1` in `?b`, and have the children `?b` and `?t`"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RewriteError {}
+
+// Checks the rule `lhs => rhs where cond` for scoping problems.
+pub(crate) fn validate_rule<L: Language>(
+    lhs: &Pattern<L>,
+    rhs: &Pattern<L>,
+    cond: Option<&Condition>,
+) -> Result<(), RewriteError> {
+    if let Some(p) = find(lhs, &|p| matches!(p, Pattern::Subst(..))) {
+        return Err(RewriteError::SubstInLhs(p.to_string()));
+    }
+    if let Some(p) = find(rhs, &|p| matches!(p, Pattern::Wildcard | Pattern::Or(_))) {
+        return Err(RewriteError::UnsupportedInRhs(p.to_string()));
+    }
+
+    let bound = lhs_pvars(lhs);
+    let mut used = Vec::new();
+    rhs_pvars(rhs, &mut used);
+    if let Some(c) = cond {
+        used.extend(c.pvars());
+    }
+    if let Some(v) = used.into_iter().find(|v| !bound.contains(v)) {
+        return Err(RewriteError::UnboundPVar(v));
+    }
+
+    let params = ho_params(lhs);
+    if let Some(f) = ho_params(rhs).into_keys().find(|f| !params.contains_key(f)) {
+        return Err(RewriteError::NotHigherOrder(f));
+    }
+    if let Some(p) = find(rhs, &|p| {
+        ho_differs(p, &params, |ps, args| ps.len() != args.len())
+    }) {
+        return Err(RewriteError::HOArity(p.to_string()));
+    }
+    let kinds_differ = |ps: &[HOArg<L>], args: &[HOArg<L>]| {
+        let kind = std::mem::discriminant::<HOArg<L>>;
+        ps.iter().zip(args).any(|(p, a)| kind(p) != kind(a))
+    };
+    if let Some(p) = find(rhs, &|p| ho_differs(p, &params, kinds_differ)) {
+        return Err(RewriteError::HOArgKind(p.to_string()));
+    }
+
+    let mut arities = HashMap::default();
+    for pat in [lhs, rhs] {
+        if let Some(op) = op_arity_mismatch(pat, &mut arities) {
+            return Err(RewriteError::OpArity(op));
+        }
+    }
+
+    let l = SlotUsage::of(lhs)?;
+    let r = SlotUsage::of(rhs)?;
+    let lhs_slots: HashSet<Slot> = l.free.union(&l.bound).copied().collect();
+    let mut free = r.free;
+    if let Some(c) = cond {
+        free.extend(c.slots());
+    }
+    if let Some(s) = free.into_iter().find(|s| !lhs_slots.contains(s)) {
+        return Err(RewriteError::UnboundSlot(s));
+    }
+    Ok(())
+}
+
+// Checks the multi-pattern rule `lhs => ?a == pat, ...` for scoping problems.
+pub(crate) fn validate_multi_rule<L: Language>(
+    lhs: &MultiPattern<L>,
+    eqs: &[(PVar, Pattern<L>)],
+) -> Result<(), RewriteError> {
+    let bound = lhs.pvars();
+    let lhs_slots: HashSet<Slot> = lhs
+        .pats
+        .iter()
+        .flat_map(|(_, n, _)| n.all_slot_occurrences())
+        .collect();
+    for (v, rhs) in eqs {
+        if let Some(p) = find(rhs, &|p| matches!(p, Pattern::Wildcard | Pattern::Or(_))) {
+            return Err(RewriteError::UnsupportedInRhs(p.to_string()));
+        }
+        // multi-patterns have no higher-order pattern variables.
+        if let Some(f) = ho_params(rhs).into_keys().next() {
+            return Err(RewriteError::NotHigherOrder(f));
+        }
+
+        let mut used = vec![v.clone()];
+        rhs_pvars(rhs, &mut used);
+        if let Some(v) = used.into_iter().find(|v| !bound.contains(v)) {
+            return Err(RewriteError::UnboundPVar(v));
+        }
+
+        let r = SlotUsage::of(rhs)?;
+        if let Some(s) = r.free.into_iter().find(|s| !lhs_slots.contains(s)) {
+            return Err(RewriteError::UnboundSlot(s));
+        }
+    }
+    Ok(())
+}
+
+// Whether every slot of `pat` is used consistently, i.e. either only as a binder or only free.
+pub(crate) fn is_well_scoped<L: Language>(pat: &Pattern<L>) -> bool {
+    SlotUsage::of(pat).is_ok()
+}
+
+// the slots bound by the e-node `n`, i.e. its private slots.
+pub(crate) fn private_slots<L: Language>(n: &L) -> Vec<Slot> {
+    let n = nullify_app_ids(n);
+    let public = n.public_slot_occurrences();
+    n.all_slot_occurrences()
+        .into_iter()
+        .filter(|s| !public.contains(s))
+        .collect()
+}
+
+// the binders of `n` whose scope contains the `i`-th child of `n`.
+pub(crate) fn child_binders<L: Language>(n: &L, i: usize) -> Vec<Slot> {
+    private_slots(n)
+        .into_iter()
+        .filter(|s| {
+            // if the child uses `s`, then `s` stays private iff `n` binds it in this child.
+            let mut n = nullify_app_ids(n);
+            *n.applied_id_occurrences_mut()[i] =
+                AppliedId::new(Id(0), SlotMap::from_pairs(&[(*s, *s)]));
+            !n.public_slot_occurrences().contains(s)
+        })
+        .collect()
+}
+
+// whether `p` is some `?f[..]`, whose arguments `differ` from the parameters of `?f` on the lhs.
+fn ho_differs<L: Language>(
+    p: &Pattern<L>,
+    params: &HashMap<String, Vec<HOArg<L>>>,
+    differ: impl Fn(&[HOArg<L>], &[HOArg<L>]) -> bool,
+) -> bool {
+    match p {
+        Pattern::HOVar(f, args) => params.get(f).is_some_and(|ps| differ(ps, args)),
+        _ => false,
+    }
+}
+
+// records the number of children of each operator variable in `pat` in `arities`,
+// and returns an operator variable that is used with different numbers of children.
+fn op_arity_mismatch<L: Language>(
+    pat: &Pattern<L>,
+    arities: &mut HashMap<String, usize>,
+) -> Option<String> {
+    if let Pattern::OpVar(op, children) = pat {
+        if *arities.entry(op.clone()).or_insert(children.len()) != children.len() {
+            return Some(op.clone());
+        }
+    }
+    match pat {
+        Pattern::ENode(_, children) | Pattern::Or(children) | Pattern::OpVar(_, children) => {
+            children.iter().find_map(|c| op_arity_mismatch(c, arities))
+        }
+        Pattern::Subst(b, sigma) => {
+            subst_parts(b, sigma).find_map(|c| op_arity_mismatch(c, arities))
+        }
+        Pattern::HOVar(_, args) => args.iter().find_map(|a| match a {
+            HOArg::Term(t) => op_arity_mismatch(t, arities),
+            HOArg::Slot(_) => None,
+        }),
+        Pattern::PVar(_) | Pattern::Wildcard => None,
+    }
+}
+
+// finds a sub-pattern of `pat` satisfying `f`.
+fn find<'a, L: Language>(
+    pat: &'a Pattern<L>,
+    f: &impl Fn(&Pattern<L>) -> bool,
+) -> Option<&'a Pattern<L>> {
+    if f(pat) {
+        return Some(pat);
+    }
+    match pat {
+        Pattern::ENode(_, children) | Pattern::Or(children) | Pattern::OpVar(_, children) => {
+            children.iter().find_map(|c| find(c, f))
+        }
+        Pattern::Subst(b, sigma) => subst_parts(b, sigma).find_map(|c| find(c, f)),
+        Pattern::HOVar(_, args) => args.iter().find_map(|a| match a {
+            HOArg::Term(t) => find(t, f),
+            HOArg::Slot(_) => None,
+        }),
+        Pattern::PVar(_) | Pattern::Wildcard => None,
+    }
+}
+
+// the pattern variables bound by every match of `pat`.
+fn lhs_pvars<L: Language>(pat: &Pattern<L>) -> HashSet<String> {
+    match pat {
+        Pattern::ENode(_, children) => children.iter().flat_map(lhs_pvars).collect(),
+        Pattern::PVar(v) | Pattern::HOVar(v, _) => [v.clone()].into_iter().collect(),
+        Pattern::OpVar(v, children) => {
+            let mut out: HashSet<String> = children.iter().flat_map(lhs_pvars).collect();
+            out.insert(v.clone());
+            out
+        }
+        Pattern::Or(alts) => {
+            let mut alts = alts.iter().map(lhs_pvars);
+            let first = alts.next().unwrap_or_default();
+            alts.fold(first, |acc, x| acc.intersection(&x).cloned().collect())
+        }
+        Pattern::Subst(..) | Pattern::Wildcard => HashSet::default(),
+    }
+}
+
+// the pattern variables used by the rhs `pat`.
+pub(crate) fn rhs_pvars<L: Language>(pat: &Pattern<L>, out: &mut Vec<String>) {
+    match pat {
+        Pattern::ENode(_, children) | Pattern::Or(children) => {
+            children.iter().for_each(|c| rhs_pvars(c, out))
+        }
+        Pattern::PVar(v) => out.push(v.clone()),
+        Pattern::OpVar(v, children) => {
+            out.push(v.clone());
+            children.iter().for_each(|c| rhs_pvars(c, out));
+        }
+        Pattern::HOVar(v, args) => {
+            out.push(v.clone());
+            for a in args {
+                if let HOArg::Term(t) = a {
+                    rhs_pvars(t, out);
+                }
+            }
+        }
+        Pattern::Subst(b, sigma) => subst_parts(b, sigma).for_each(|c| rhs_pvars(c, out)),
+        Pattern::Wildcard => {}
+    }
+}
+
+// The slots of a pattern, split into the free slots and the slots bound by some e-node.
+#[derive(Default)]
+struct SlotUsage {
+    free: HashSet<Slot>,
+    bound: HashSet<Slot>,
+}
+
+impl SlotUsage {
+    fn of<L: Language>(pat: &Pattern<L>) -> Result<SlotUsage, RewriteError> {
+        let mut out = SlotUsage::default();
+        out.scan(pat, &mut Vec::new())?;
+        if let Some(s) = out.free.intersection(&out.bound).next() {
+            return Err(RewriteError::BoundAndFree(*s));
+        }
+        Ok(out)
+    }
+
+    fn use_slot(&mut self, s: Slot, scope: &[Slot]) {
+        if !scope.contains(&s) {
+            self.free.insert(s);
+        }
+    }
+
+    // `scope` are the slots bound by the surrounding e-nodes.
+    fn scan<L: Language>(
+        &mut self,
+        pat: &Pattern<L>,
+        scope: &mut Vec<Slot>,
+    ) -> Result<(), RewriteError> {
+        match pat {
+            Pattern::ENode(n, children) => {
+                for s in nullify_app_ids(n).public_slot_occurrences() {
+                    self.use_slot(s, scope);
+                }
+                let private = private_slots(n);
+                for s in &private {
+                    if scope.contains(s) {
+                        return Err(RewriteError::BoundTwice(*s));
+                    }
+                    self.bound.insert(*s);
+                }
+
+                let n = scope.len();
+                scope.extend(private);
+                for c in children {
+                    self.scan(c, scope)?;
+                }
+                scope.truncate(n);
+            }
+            Pattern::HOVar(_, args) => {
+                for a in args {
+                    match a {
+                        HOArg::Slot(s) => self.use_slot(*s, scope),
+                        HOArg::Term(t) => self.scan(t, scope)?,
+                    }
+                }
+            }
+            Pattern::Subst(b, sigma) => {
+                for c in subst_parts(b, sigma) {
+                    self.scan(c, scope)?;
+                }
+            }
+            Pattern::Or(children) | Pattern::OpVar(_, children) => {
+                for c in children {
+                    self.scan(c, scope)?;
+                }
+            }
+            Pattern::PVar(_) | Pattern::Wildcard => {}
+        }
+        Ok(())
+    }
+}
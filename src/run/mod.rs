@@ -8,3 +8,6 @@ pub use run::*;
 
 mod runner;
 pub use runner::*;
+
+mod scheduler;
+pub use scheduler::*;
@@ -1,4 +1,8 @@
+use crate::HashMap;
+use std::time::Duration;
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StopReason<T = String>
 where
     T: Clone,
@@ -7,10 +11,18 @@ where
     IterationLimit,
     TimeLimit,
     NodeLimit,
+    /// All goals of the [Runner](crate::Runner) hold, see [Runner::with_goal](crate::Runner::with_goal).
+    GoalReached,
+    /// A phase reached its own iteration limit, see [Runner::with_limited_phase](crate::Runner::with_limited_phase).
+    PhaseLimit,
+    /// [Runner::run_phases](crate::Runner::run_phases) repeated its schedule [Runner::phase_repeats](crate::Runner::phase_repeats) times,
+    /// and the last round still changed the e-graph.
+    RepeatLimit,
     Other(T),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Report<T = String>
 where
     T: Clone,
@@ -20,4 +32,52 @@ where
     pub egraph_nodes: usize,
     pub egraph_classes: usize,
     pub total_time: f64,
+    /// The [RuleStats] of each rule by its name, summed over all iterations.
+    ///
+    /// The names of the rules are distinct, see [Runner::run](crate::Runner::run).
+    pub rule_stats: HashMap<String, RuleStats>,
+    /// The hit statistics of the substitution cache, if the e-graph uses a [MemoSubst](crate::MemoSubst).
+    pub subst_cache: Option<crate::SubstCacheStats>,
+    /// The [PhaseReport] of each phase, if the runner ran phases, see [Runner::run_phases](crate::Runner::run_phases).
+    pub phases: Vec<PhaseReport<T>>,
+    /// The proof of each goal, if the runner stopped with [StopReason::GoalReached].
+    #[cfg(feature = "explanations")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub goal_proofs: Vec<crate::ProvenEq>,
+}
+
+/// How a phase of [Runner::run_phases](crate::Runner::run_phases) ended.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaseReport<T = String>
+where
+    T: Clone,
+{
+    /// The name of the rule set of this phase.
+    pub rule_set: String,
+    /// The repetition of the phase schedule, starting at 0.
+    pub round: usize,
+    pub iterations: usize,
+    pub stop_reason: StopReason<T>,
+}
+
+/// How productive a [Rewrite](crate::Rewrite) was, see [Iteration::rule_stats](crate::Iteration::rule_stats) and [Report::rule_stats].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuleStats {
+    /// The number of matches found by the searcher, if it counts them, see [SearchMatches::count](crate::SearchMatches::count).
+    pub matches: usize,
+    /// The number of unions done by the applier that changed the e-graph, see [EGraph::union_count](crate::EGraph::union_count).
+    pub unions: usize,
+    pub search_time: Duration,
+    pub apply_time: Duration,
+}
+
+impl std::ops::AddAssign for RuleStats {
+    fn add_assign(&mut self, other: RuleStats) {
+        self.matches += other.matches;
+        self.unions += other.unions;
+        self.search_time += other.search_time;
+        self.apply_time += other.apply_time;
+    }
 }
@@ -16,9 +16,13 @@ where
     let start_time = Instant::now();
     let mut iterations = 0;
     let stop_reason: StopReason;
+    let mut rule_stats: HashMap<String, RuleStats> = HashMap::default();
 
     loop {
-        let did_change = apply_rewrites(egraph, &rws);
+        let (did_change, stats) = apply_rewrites_with_stats(egraph, &rws);
+        for (name, s) in stats {
+            *rule_stats.entry(name).or_default() += s;
+        }
 
         match hook(egraph) {
             Ok(_) => (),
@@ -52,5 +56,10 @@ where
         egraph_nodes: egraph.total_number_of_nodes(),
         egraph_classes: egraph.ids().len(),
         total_time: start_time.elapsed().as_secs_f64(),
+        rule_stats,
+        subst_cache: egraph.subst_cache_stats(),
+        phases: Vec::new(),
+        #[cfg(feature = "explanations")]
+        goal_proofs: Vec::new(),
     }
 }
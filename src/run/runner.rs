@@ -2,12 +2,33 @@ use crate::*;
 use std::time::Duration;
 use std::time::Instant;
 
+/// What happened in an iteration of a [Runner].
+///
+/// With the `serde` feature, iterations can be serialized, e.g. to plot the convergence of a run.
+/// The `finish_time` is skipped, as an [Instant] can't be serialized.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Iteration<IterData> {
     /// The user provided annotation for this iteration
     pub data: IterData,
-    // TODO: add more data things
     pub num_nodes: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub finish_time: Option<Instant>,
+    /// The [ProgressMeasure] of the e-graph after this iteration.
+    pub progress: ProgressMeasure,
+    /// The time spent searching for matches.
+    pub search_time: Duration,
+    /// The time spent applying the matches, without rebuilding.
+    pub apply_time: Duration,
+    /// The time spent rebuilding the e-graph.
+    pub rebuild_time: Duration,
+    /// The number of unions that changed the e-graph.
+    pub num_unions: usize,
+    /// The largest size of the `pending` worklist of the e-graph while rebuilding.
+    pub max_pending: usize,
+    /// The names of the rules that the [Scheduler] banned in this iteration.
+    pub banned_rules: Vec<String>,
+    /// The [RuleStats] of each rule that was not banned in this iteration.
+    pub rule_stats: HashMap<String, RuleStats>,
 }
 pub trait IterationData<L, N>: Sized
 where
@@ -33,6 +54,15 @@ where
     }
 }
 
+/// A phase of [Runner::run_phases], which runs the rules of a rule set.
+#[derive(Debug, Clone)]
+pub struct Phase {
+    /// The name of the rule set, see [Runner::with_rule_set].
+    pub rule_set: String,
+    /// The maximal number of iterations of this phase, or `None` to run it until saturation.
+    pub iter_limit: Option<usize>,
+}
+
 pub struct RunnerLimits {
     iter_limit: usize,
     node_limit: usize,
@@ -86,6 +116,16 @@ where
     pub limits: RunnerLimits,
     /// hooks
     pub hooks: Vec<Box<dyn FnMut(&mut Self) -> Result<(), CustomErrorT> + 'static>>,
+    /// Decides which rules are searched & applied in each iteration.
+    pub scheduler: Box<dyn Scheduler<L, N>>,
+    /// The named rule sets used by the [`phases`](Runner::phases).
+    pub rule_sets: HashMap<String, Vec<Rewrite<L, N>>>,
+    /// The schedule of [Runner::run_phases].
+    pub phases: Vec<Phase>,
+    /// How often [Runner::run_phases] repeats the schedule at most.
+    pub phase_repeats: usize,
+    /// The equations to prove, see [Runner::with_goal].
+    pub goals: Vec<(RecExpr<L>, RecExpr<L>)>,
 }
 
 impl<L, N, IterData, CustomErrorT> Runner<L, N, IterData, CustomErrorT>
@@ -109,6 +149,11 @@ where
             },
             hooks: vec![],
             roots: vec![],
+            scheduler: Box::new(SimpleScheduler),
+            rule_sets: HashMap::default(),
+            phases: vec![],
+            phase_repeats: 1,
+            goals: vec![],
         }
     }
     pub fn with_expr(mut self, expr: &RecExpr<L>) -> Self {
@@ -123,6 +168,10 @@ where
         self.hooks.push(Box::new(hook));
         self
     }
+    pub fn with_scheduler(mut self, scheduler: impl Scheduler<L, N> + 'static) -> Self {
+        self.scheduler = Box::new(scheduler);
+        self
+    }
     pub fn with_egraph(mut self, egraph: EGraph<L, N>) -> Self {
         // You should probably not use this if you use `with_expr` as well
         self.egraph = egraph;
@@ -140,12 +189,85 @@ where
         self.limits.time_limit = time_limit;
         self
     }
+    /// Adds the goal `lhs = rhs`: the runner stops with [StopReason::GoalReached] as soon as all goals hold.
+    ///
+    /// Both terms are added to the e-graph.
+    /// With the `explanations` feature, the [Report] contains a proof of each goal.
+    pub fn with_goal(mut self, lhs: RecExpr<L>, rhs: RecExpr<L>) -> Self {
+        self.egraph.add_expr(lhs.clone());
+        self.egraph.add_expr(rhs.clone());
+        self.goals.push((lhs, rhs));
+        self
+    }
+    /// Adds all the `goals`, see [Runner::with_goal].
+    pub fn with_goals(self, goals: impl IntoIterator<Item = (RecExpr<L>, RecExpr<L>)>) -> Self {
+        goals
+            .into_iter()
+            .fold(self, |runner, (lhs, rhs)| runner.with_goal(lhs, rhs))
+    }
+    /// Adds the rule set `name`, which can then be used by phases.
+    ///
+    /// Panics if two of the `rewrites` have the same name.
+    pub fn with_rule_set(mut self, name: &str, rewrites: Vec<Rewrite<L, N>>) -> Self {
+        assert_distinct_names(&rewrites);
+        self.rule_sets.insert(name.to_string(), rewrites);
+        self
+    }
+    /// Adds a phase to the schedule, which runs the rule set `rule_set` until saturation.
+    ///
+    /// The limits of the runner (e.g. [Runner::with_iter_limit]) still apply to all phases together.
+    pub fn with_phase(self, rule_set: &str) -> Self {
+        self.with_phase_impl(rule_set, None)
+    }
+    /// Adds a phase to the schedule, which runs the rule set `rule_set` until saturation, or for at most `iter_limit` iterations.
+    pub fn with_limited_phase(self, rule_set: &str, iter_limit: usize) -> Self {
+        self.with_phase_impl(rule_set, Some(iter_limit))
+    }
+    fn with_phase_impl(mut self, rule_set: &str, iter_limit: Option<usize>) -> Self {
+        assert!(
+            self.rule_sets.contains_key(rule_set),
+            "unknown rule set `{rule_set}`"
+        );
+        self.phases.push(Phase {
+            rule_set: rule_set.to_string(),
+            iter_limit,
+        });
+        self
+    }
+    /// Repeats the schedule of phases up to `repeats` times (default 1).
+    ///
+    /// The repetition stops early, once a whole round of phases doesn't change the e-graph.
+    /// Panics if `repeats` is 0.
+    pub fn with_phase_repeats(mut self, repeats: usize) -> Self {
+        assert!(repeats > 0, "the phases have to run at least once");
+        self.phase_repeats = repeats;
+        self
+    }
 
     fn check_limits(&mut self) -> RunnerResult<(), CustomErrorT> {
         self.limits
             .check_limits(self.iterations.len(), &self.egraph)
     }
+    fn check_goals(&self) -> RunnerResult<(), CustomErrorT> {
+        let holds = |(lhs, rhs): &(RecExpr<L>, RecExpr<L>)| match (
+            lookup_rec_expr(lhs, &self.egraph),
+            lookup_rec_expr(rhs, &self.egraph),
+        ) {
+            (Some(l), Some(r)) => self.egraph.eq(&l, &r),
+            _ => false,
+        };
+        if !self.goals.is_empty() && self.goals.iter().all(holds) {
+            Err(StopReason::GoalReached)
+        } else {
+            Ok(())
+        }
+    }
+    /// Runs the `rewrites` until the runner stops, see [StopReason].
+    ///
+    /// The rules are identified by their names, e.g. by the [Scheduler] and in the [RuleStats],
+    /// so this panics if two of the `rewrites` have the same name.
     pub fn run(&mut self, rewrites: &[Rewrite<L, N>]) -> Report<CustomErrorT> {
+        assert_distinct_names(rewrites);
         loop {
             if let Some(_) = self.stop_reason {
                 break;
@@ -153,6 +275,73 @@ where
             let iter = self.run_one(rewrites);
             self.iterations.push(iter);
         }
+        self.report(Vec::new())
+    }
+    /// Runs the phases of the schedule one after the other, see [Runner::with_phase].
+    ///
+    /// Each phase stops when its rule set saturates, or with [StopReason::PhaseLimit] when it reaches its own iteration limit.
+    /// The stop reason of each phase is recorded in [Report::phases].
+    /// If a limit of the runner or a hook stops a phase, all phases stop.
+    /// Otherwise, the runner stops with [StopReason::Saturated] once a whole round of phases doesn't change the e-graph,
+    /// and with [StopReason::RepeatLimit] if the schedule has been repeated [Runner::phase_repeats] times.
+    pub fn run_phases(&mut self) -> Report<CustomErrorT> {
+        assert!(!self.phases.is_empty(), "the Runner has no phases");
+        let phases = self.phases.clone();
+
+        let mut reports: Vec<PhaseReport<CustomErrorT>> = Vec::new();
+        'rounds: for round in 0..self.phase_repeats {
+            if self.stop_reason.is_some() {
+                break;
+            }
+            let prog = self.egraph.progress();
+            for phase in &phases {
+                let mut iterations = 0;
+                let stop_reason = loop {
+                    let iter = self.run_one_in(&phase.rule_set);
+                    self.iterations.push(iter);
+                    iterations += 1;
+                    match self.stop_reason.take() {
+                        Some(StopReason::Saturated) => break StopReason::Saturated,
+                        Some(r) => {
+                            self.stop_reason = Some(r.clone());
+                            break r;
+                        }
+                        None if phase.iter_limit.is_some_and(|l| iterations >= l) => {
+                            break StopReason::PhaseLimit;
+                        }
+                        None => {}
+                    }
+                };
+                reports.push(PhaseReport {
+                    rule_set: phase.rule_set.clone(),
+                    round,
+                    iterations,
+                    stop_reason,
+                });
+                if self.stop_reason.is_some() {
+                    break 'rounds;
+                }
+            }
+            if self.egraph.progress() == prog {
+                self.stop_reason = Some(StopReason::Saturated);
+            }
+        }
+        if self.stop_reason.is_none() {
+            self.stop_reason = Some(StopReason::RepeatLimit);
+        }
+        self.report(reports)
+    }
+    fn report(&mut self, phases: Vec<PhaseReport<CustomErrorT>>) -> Report<CustomErrorT> {
+        #[cfg(feature = "explanations")]
+        let goal_proofs = match self.stop_reason {
+            Some(StopReason::GoalReached) => self
+                .goals
+                .clone()
+                .into_iter()
+                .map(|(lhs, rhs)| self.egraph.explain_equivalence(lhs, rhs))
+                .collect(),
+            _ => Vec::new(),
+        };
         Report {
             iterations: self.iterations.len(),
             stop_reason: self.stop_reason.clone().unwrap(),
@@ -166,9 +355,32 @@ where
                 .unwrap()
                 .duration_since(self.limits.start_time.unwrap())
                 .as_secs_f64(),
+            rule_stats: self.total_rule_stats(),
+            subst_cache: self.egraph.subst_cache_stats(),
+            phases,
+            #[cfg(feature = "explanations")]
+            goal_proofs,
         }
     }
     fn run_one(&mut self, rewrites: &[Rewrite<L, N>]) -> Iteration<IterData> {
+        self.run_one_with(|runner| runner.apply_scheduled_rewrites(rewrites))
+    }
+
+    // Like `run_one`, but with the rules of the rule set `rule_set`.
+    // The rule set is only taken out of `rule_sets` while its rules are applied, so the hooks see all rule sets.
+    fn run_one_in(&mut self, rule_set: &str) -> Iteration<IterData> {
+        self.run_one_with(|runner| {
+            let rewrites = runner.rule_sets.remove(rule_set).unwrap();
+            let out = runner.apply_scheduled_rewrites(&rewrites);
+            runner.rule_sets.insert(rule_set.to_string(), rewrites);
+            out
+        })
+    }
+
+    fn run_one_with(
+        &mut self,
+        apply: impl FnOnce(&mut Self) -> (bool, Vec<String>, HashMap<String, RuleStats>),
+    ) -> Iteration<IterData> {
         assert!(self.stop_reason.is_none());
 
         // if the runner has not started, start the timer
@@ -176,10 +388,14 @@ where
         let mut hooks = std::mem::take(&mut self.hooks);
 
         let mut result = Ok(());
+        let rebuild_time = self.egraph.rebuild_time;
+        let unions = self.egraph.union_count();
+        self.egraph.max_pending = 0;
 
-        // Apply rewrites, then check hooks, then check limits, then check if saturated.
-        let progress = apply_rewrites(&mut self.egraph, rewrites);
+        // Apply rewrites, then check goals, then check hooks, then check limits, then check if saturated.
+        let (progress, banned_rules, rule_stats) = apply(self);
         result = result
+            .and_then(|_| self.check_goals())
             .and_then(|_| {
                 hooks
                     .iter_mut()
@@ -187,7 +403,7 @@ where
             })
             .and_then(|_| self.check_limits());
 
-        if !progress {
+        if !progress && self.scheduler.can_stop(self.iterations.len()) {
             result = result.and_then(|_| Err(StopReason::Saturated));
         }
 
@@ -195,11 +411,71 @@ where
             self.stop_reason = Some(stop_reason);
         }
         self.hooks = hooks;
+
+        let rebuild_time = self.egraph.rebuild_time - rebuild_time;
+        let apply_time: Duration = rule_stats.values().map(|s| s.apply_time).sum();
         Iteration {
             data: IterData::make(self),
             num_nodes: self.egraph.total_number_of_nodes(),
             finish_time: Some(Instant::now()),
+            progress: self.egraph.progress(),
+            search_time: rule_stats.values().map(|s| s.search_time).sum(),
+            apply_time: apply_time.saturating_sub(rebuild_time),
+            rebuild_time,
+            num_unions: self.egraph.union_count() - unions,
+            max_pending: self.egraph.max_pending,
+            banned_rules,
+            rule_stats,
+        }
+    }
+
+    // Like `apply_rewrites_with_stats`, but asks the scheduler which rules to search & apply.
+    // Also returns the names of the banned rules.
+    fn apply_scheduled_rewrites(
+        &mut self,
+        rewrites: &[Rewrite<L, N>],
+    ) -> (bool, Vec<String>, HashMap<String, RuleStats>) {
+        let iteration = self.iterations.len();
+        let prog = self.egraph.progress();
+
+        let mut banned_rules = Vec::new();
+        let mut rule_stats: HashMap<String, RuleStats> = HashMap::default();
+        let mut matches = Vec::new();
+        for rw in rewrites {
+            let start = Instant::now();
+            match self.scheduler.search_rewrite(iteration, &self.egraph, rw) {
+                Some(m) => {
+                    let stats = rule_stats.entry(rw.name().to_string()).or_default();
+                    stats.search_time += start.elapsed();
+                    stats.matches += m.len();
+                    matches.push((rw, m));
+                }
+                None => banned_rules.push(rw.name().to_string()),
+            }
+        }
+
+        for (rw, m) in matches {
+            let start = Instant::now();
+            let unions = self.egraph.union_count();
+            self.scheduler
+                .apply_rewrite(iteration, &mut self.egraph, rw, m);
+            let stats = rule_stats.get_mut(rw.name()).unwrap();
+            stats.apply_time += start.elapsed();
+            stats.unions += self.egraph.union_count() - unions;
+        }
+
+        (prog != self.egraph.progress(), banned_rules, rule_stats)
+    }
+
+    // sums up the `rule_stats` of all iterations.
+    fn total_rule_stats(&self) -> HashMap<String, RuleStats> {
+        let mut out: HashMap<String, RuleStats> = HashMap::default();
+        for it in &self.iterations {
+            for (name, stats) in &it.rule_stats {
+                *out.entry(name.clone()).or_default() += *stats;
+            }
         }
+        out
     }
 }
 
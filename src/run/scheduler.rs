@@ -0,0 +1,205 @@
+use crate::*;
+
+/// A Scheduler decides which [Rewrite]s are searched and applied in each iteration of a [Runner].
+pub trait Scheduler<L: Language, N: Analysis<L>> {
+    /// Whether the [Runner] is allowed to stop due to saturation.
+    ///
+    /// Schedulers that ban rules should return `false` here while some rules are still banned,
+    /// as the e-graph is not actually saturated in that case.
+    fn can_stop(&mut self, _iteration: usize) -> bool {
+        true
+    }
+
+    /// Searches for the matches of `rewrite`.
+    ///
+    /// Returns `None` if the rule is banned in this iteration.
+    fn search_rewrite(
+        &mut self,
+        _iteration: usize,
+        eg: &EGraph<L, N>,
+        rewrite: &Rewrite<L, N>,
+    ) -> Option<SearchMatches> {
+        Some(rewrite.search(eg))
+    }
+
+    /// Applies the matches previously found by [Scheduler::search_rewrite].
+    fn apply_rewrite(
+        &mut self,
+        _iteration: usize,
+        eg: &mut EGraph<L, N>,
+        rewrite: &Rewrite<L, N>,
+        matches: SearchMatches,
+    ) {
+        rewrite.apply(matches, eg);
+    }
+}
+
+/// A [Scheduler] that searches and applies every rule in every iteration.
+#[derive(Default)]
+pub struct SimpleScheduler;
+
+impl<L: Language, N: Analysis<L>> Scheduler<L, N> for SimpleScheduler {}
+
+/// A [Scheduler] that bans rules which find too many matches, similar to egg's `BackoffScheduler`.
+///
+/// A rule that finds more than its match limit is banned for its ban length.
+/// Every time a rule gets banned, its match limit and ban length are doubled.
+///
+/// For rules which don't count their matches (see [SearchMatches::count]), the number of unions done by the applier is used instead.
+/// Such a rule is banned after it has been applied.
+///
+/// The rules are identified by their names, which the [Runner] requires to be distinct.
+pub struct BackoffScheduler {
+    default_match_limit: usize,
+    default_ban_length: usize,
+    stats: HashMap<String, RuleBackoff>,
+}
+
+struct RuleBackoff {
+    times_applied: usize,
+    banned_until: usize,
+    times_banned: usize,
+    match_limit: usize,
+    ban_length: usize,
+}
+
+impl BackoffScheduler {
+    /// Sets the match limit for all rules without an individual limit.
+    pub fn with_initial_match_limit(mut self, limit: usize) -> Self {
+        self.default_match_limit = limit;
+        self
+    }
+
+    /// Sets the ban length for all rules without an individual ban length.
+    pub fn with_ban_length(mut self, ban_length: usize) -> Self {
+        self.default_ban_length = ban_length;
+        self
+    }
+
+    /// Sets the match limit of the rule `name`.
+    pub fn with_rule_limit(mut self, name: &str, limit: usize) -> Self {
+        self.rule_stats(name).match_limit = limit;
+        self
+    }
+
+    /// Sets the ban length of the rule `name`.
+    pub fn with_rule_ban_length(mut self, name: &str, ban_length: usize) -> Self {
+        self.rule_stats(name).ban_length = ban_length;
+        self
+    }
+
+    /// Never bans the rule `name`.
+    pub fn do_not_ban(self, name: &str) -> Self {
+        self.with_rule_limit(name, usize::MAX)
+    }
+
+    /// How often the rule `name` has been banned so far.
+    pub fn times_banned(&self, name: &str) -> usize {
+        self.stats.get(name).map(|s| s.times_banned).unwrap_or(0)
+    }
+
+    /// How often the rule `name` has been applied so far.
+    pub fn times_applied(&self, name: &str) -> usize {
+        self.stats.get(name).map(|s| s.times_applied).unwrap_or(0)
+    }
+
+    // bans the rule `name` if `matches` exceeds its current limit, and returns whether it got banned.
+    fn ban_if_exceeded(&mut self, name: &str, iteration: usize, matches: usize) -> bool {
+        let stats = self.rule_stats(name);
+        let factor = 1usize
+            .checked_shl(stats.times_banned as u32)
+            .unwrap_or(usize::MAX);
+        if matches <= stats.match_limit.saturating_mul(factor) {
+            return false;
+        }
+        let ban_length = stats.ban_length.saturating_mul(factor);
+        stats.times_banned += 1;
+        stats.banned_until = iteration.saturating_add(ban_length);
+        true
+    }
+
+    fn rule_stats(&mut self, name: &str) -> &mut RuleBackoff {
+        let (match_limit, ban_length) = (self.default_match_limit, self.default_ban_length);
+        self.stats
+            .entry(name.to_string())
+            .or_insert_with(|| RuleBackoff {
+                times_applied: 0,
+                banned_until: 0,
+                times_banned: 0,
+                match_limit,
+                ban_length,
+            })
+    }
+}
+
+impl Default for BackoffScheduler {
+    fn default() -> Self {
+        Self {
+            default_match_limit: 1_000,
+            default_ban_length: 5,
+            stats: HashMap::default(),
+        }
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Scheduler<L, N> for BackoffScheduler {
+    fn can_stop(&mut self, iteration: usize) -> bool {
+        let banned: Vec<&mut RuleBackoff> = self
+            .stats
+            .values_mut()
+            .filter(|s| s.banned_until > iteration)
+            .collect();
+
+        if banned.is_empty() {
+            return true;
+        }
+
+        // Some rules are still banned, so we are not saturated yet.
+        // We shorten all bans, so that the first banned rule is available in the next iteration.
+        let min_ban = banned
+            .iter()
+            .map(|s| s.banned_until - iteration)
+            .min()
+            .unwrap();
+        for s in banned {
+            s.banned_until -= min_ban;
+        }
+
+        false
+    }
+
+    fn search_rewrite(
+        &mut self,
+        iteration: usize,
+        eg: &EGraph<L, N>,
+        rewrite: &Rewrite<L, N>,
+    ) -> Option<SearchMatches> {
+        if iteration < self.rule_stats(rewrite.name()).banned_until {
+            return None;
+        }
+
+        let matches = rewrite.search(eg);
+        if let Some(n) = matches.count() {
+            if self.ban_if_exceeded(rewrite.name(), iteration, n) {
+                return None;
+            }
+        }
+        self.rule_stats(rewrite.name()).times_applied += 1;
+        Some(matches)
+    }
+
+    fn apply_rewrite(
+        &mut self,
+        iteration: usize,
+        eg: &mut EGraph<L, N>,
+        rewrite: &Rewrite<L, N>,
+        matches: SearchMatches,
+    ) {
+        let counted = matches.count().is_some();
+        let unions = eg.union_count();
+        rewrite.apply(matches, eg);
+        if !counted {
+            self.ban_if_exceeded(rewrite.name(), iteration, eg.union_count() - unions);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::*;
+
+// evaluates a term on the `i`-th sample, see [Synthesizer::new].
+type Evaluator<L, V> = Box<dyn Fn(&Pattern<L>, usize) -> Option<V>>;
+
+/// A Ruler-style synthesizer of rewrite rules, which also handles languages with binders.
+///
+/// It enumerates all well-scoped terms up to a size bound, built from atoms (e.g. `?a`, `0`, `(var $1)`)
+/// and operators (e.g. `(add ?0 ?1)`, `(let $1 ?0 ?1)`), and evaluates each term on a number of samples.
+/// Terms with the same characteristic vector (i.e. the same values on all samples) are considered equal,
+/// and proposed as candidate rules.
+/// Finally, the candidates are minimized: a candidate is dropped if it can already be derived from the smaller rules by equality saturation.
+///
+/// Binders are taken into account when turning an equation into a rule:
+/// - A pattern variable `?x` under a binder `$1` on the lhs becomes `?x[]`, so it can't match terms using `$1`.
+///   This is exactly what the evaluation checked, as the values of pattern variables don't depend on bound variables.
+/// - If the rhs puts `?x` under a binder `$1` which doesn't surround it on the lhs, the rule gets the condition `$1 # ?x`.
+pub struct Synthesizer<L: Language, V> {
+    atoms: Vec<Pattern<L>>,
+    ops: Vec<Pattern<L>>,
+    var_node: fn(Slot) -> L,
+    samples: usize,
+    eval: Evaluator<L, V>,
+    max_size: usize,
+    iter_limit: usize,
+}
+
+impl<L: Language + 'static, V: Hash + Eq + Clone> Synthesizer<L, V> {
+    /// Creates a synthesizer without any atoms or operators.
+    ///
+    /// `eval(term, i)` evaluates the `term` on the `i`-th sample (for `i < samples`), and returns `None` if it is undefined.
+    /// The `term` only contains the configured atoms and operators, so the sample determines the values of its pattern variables.
+    ///
+    /// `var_node` constructs the variable node of the language (see [EGraph::set_var_node]).
+    /// It is used to represent pattern variables by constants when checking whether a candidate is derivable.
+    pub fn new(
+        var_node: fn(Slot) -> L,
+        samples: usize,
+        eval: impl Fn(&Pattern<L>, usize) -> Option<V> + 'static,
+    ) -> Self {
+        Synthesizer {
+            atoms: Vec::new(),
+            ops: Vec::new(),
+            var_node,
+            samples,
+            eval: Box::new(eval),
+            max_size: 3,
+            iter_limit: 4,
+        }
+    }
+
+    /// Adds an atom, e.g. `?a`, `0` or `(var $1)`. Atoms have size 1.
+    pub fn with_atom(mut self, atom: &str) -> Self {
+        self.atoms.push(Pattern::parse(atom).unwrap());
+        self
+    }
+
+    /// Adds an operator, whose holes are the pattern variables `?0`, `?1`, ..., e.g. `(add ?0 ?1)` or `(let $1 ?0 ?1)`.
+    ///
+    /// Filling the holes with terms of sizes `s0, s1, ...` gives a term of size `1 + s0 + s1 + ...`.
+    pub fn with_op(mut self, op: &str) -> Self {
+        self.ops.push(Pattern::parse(op).unwrap());
+        self
+    }
+
+    /// Sets the maximal size of the enumerated terms (default 3).
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets how many times the smaller rules are applied, when checking whether a candidate is derivable (default 4).
+    pub fn with_iter_limit(mut self, iter_limit: usize) -> Self {
+        self.iter_limit = iter_limit;
+        self
+    }
+
+    /// Enumerates all well-scoped terms up to the maximal size, ordered by size.
+    pub fn terms(&self) -> Vec<Pattern<L>> {
+        let mut by_size: Vec<Vec<Pattern<L>>> = vec![Vec::new(); self.max_size + 1];
+        if self.max_size >= 1 {
+            by_size[1] = self.atoms.clone();
+        }
+        for size in 2..=self.max_size {
+            let mut out = Vec::new();
+            for op in &self.ops {
+                let n = holes(op);
+                for sizes in compositions(size - 1, n) {
+                    let mut combos: Vec<Vec<&Pattern<L>>> = vec![Vec::new()];
+                    for s in sizes {
+                        combos = combos
+                            .into_iter()
+                            .flat_map(|c| {
+                                by_size[s].iter().map(move |t| {
+                                    let mut c = c.clone();
+                                    c.push(t);
+                                    c
+                                })
+                            })
+                            .collect();
+                    }
+                    out.extend(combos.into_iter().map(|c| plug(op, &c)));
+                }
+            }
+            out.retain(is_well_scoped);
+            by_size[size] = out;
+        }
+        by_size.into_iter().flatten().collect()
+    }
+
+    /// Synthesizes a minimal set of rules, ordered by size.
+    ///
+    /// Each rule is named after its equation, e.g. `(add ?a 0) => ?a`.
+    pub fn synthesize(&self) -> Vec<Rewrite<L>> {
+        // group the terms by their characteristic vector, in enumeration order.
+        let mut index: HashMap<Vec<V>, usize> = HashMap::default();
+        let mut groups: Vec<Vec<Pattern<L>>> = Vec::new();
+        for t in self.terms() {
+            let cvec: Option<Vec<V>> = (0..self.samples).map(|i| (self.eval)(&t, i)).collect();
+            let Some(cvec) = cvec else { continue };
+            let i = *index.entry(cvec).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[i].push(t);
+        }
+
+        let mut candidates = Vec::new();
+        for mut g in groups {
+            g.sort_by_cached_key(|t| (size(t), t.to_string()));
+            let rep = g[0].clone();
+            for t in g.into_iter().skip(1) {
+                candidates.push((t, rep.clone()));
+            }
+        }
+        // smaller candidates first, and among those the more general ones (with more pattern variables).
+        candidates.sort_by_cached_key(|(a, b)| {
+            let mut pvars = Vec::new();
+            rhs_pvars(a, &mut pvars);
+            rhs_pvars(b, &mut pvars);
+            pvars.sort();
+            pvars.dedup();
+            (
+                size(a) + size(b),
+                std::cmp::Reverse(pvars.len()),
+                a.to_string(),
+                b.to_string(),
+            )
+        });
+
+        let mut rules = Vec::new();
+        for (a, b) in candidates {
+            if !self.derivable(&rules, &a, &b) {
+                rules.extend(to_rule(&a, &b));
+                rules.extend(to_rule(&b, &a));
+            }
+        }
+        rules
+    }
+
+    // Whether `rules` prove `a = b`, where the pattern variables are replaced by distinct variables.
+    fn derivable(&self, rules: &[Rewrite<L>], a: &Pattern<L>, b: &Pattern<L>) -> bool {
+        let mut eg = EGraph::<L>::default();
+        let mut pvars = Vec::new();
+        rhs_pvars(a, &mut pvars);
+        rhs_pvars(b, &mut pvars);
+        let mut subst = Subst::default();
+        for v in pvars {
+            let x = eg.add_syn((self.var_node)(Slot::named(&format!("skolem_{v}"))));
+            subst.insert(v, x);
+        }
+        let a = pattern_subst(&mut eg, a, &subst);
+        let b = pattern_subst(&mut eg, b, &subst);
+        for _ in 0..self.iter_limit {
+            if eg.eq(&a, &b) || !apply_rewrites(&mut eg, rules) {
+                break;
+            }
+        }
+        eg.eq(&a, &b)
+    }
+}
+
+// the number of holes `?0`, `?1`, ... of an operator.
+fn holes<L: Language>(op: &Pattern<L>) -> usize {
+    let mut pvars = Vec::new();
+    rhs_pvars(op, &mut pvars);
+    pvars
+        .iter()
+        .filter_map(|v| v.parse::<usize>().ok())
+        .map(|i| i + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+// all ways to write `n` as a sum of `k` positive numbers.
+fn compositions(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return if n == 0 { vec![Vec::new()] } else { Vec::new() };
+    }
+    let mut out = Vec::new();
+    for first in 1..=n.saturating_sub(k - 1) {
+        for mut rest in compositions(n - first, k - 1) {
+            rest.insert(0, first);
+            out.push(rest);
+        }
+    }
+    out
+}
+
+// fills the holes `?i` of `op` with `args[i]`.
+fn plug<L: Language>(op: &Pattern<L>, args: &[&Pattern<L>]) -> Pattern<L> {
+    match op {
+        Pattern::ENode(n, children) => {
+            Pattern::ENode(n.clone(), children.iter().map(|c| plug(c, args)).collect())
+        }
+        Pattern::PVar(v) => match v.parse::<usize>() {
+            Ok(i) => args[i].clone(),
+            Err(_) => op.clone(),
+        },
+        _ => op.clone(),
+    }
+}
+
+fn size<L: Language>(pat: &Pattern<L>) -> usize {
+    match pat {
+        Pattern::ENode(_, children) => 1 + children.iter().map(size).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+// the pattern variables of `pat`, together with the binders surrounding them.
+fn pvar_scopes<L: Language>(
+    pat: &Pattern<L>,
+    scope: &mut Vec<Slot>,
+    out: &mut Vec<(String, Slot)>,
+) {
+    match pat {
+        Pattern::ENode(n, children) => {
+            for (i, c) in children.iter().enumerate() {
+                let k = scope.len();
+                scope.extend(child_binders(n, i));
+                pvar_scopes(c, scope, out);
+                scope.truncate(k);
+            }
+        }
+        Pattern::PVar(v) => out.extend(scope.iter().map(|s| (v.clone(), *s))),
+        _ => {}
+    }
+}
+
+// replaces the pattern variables under a binder by `?x[]`.
+fn restrict_pvars<L: Language>(pat: &Pattern<L>, under_binder: bool) -> Pattern<L> {
+    match pat {
+        Pattern::ENode(n, children) => {
+            let children = children
+                .iter()
+                .enumerate()
+                .map(|(i, c)| restrict_pvars(c, under_binder || !child_binders(n, i).is_empty()))
+                .collect();
+            Pattern::ENode(n.clone(), children)
+        }
+        Pattern::PVar(v) if under_binder => Pattern::HOVar(v.clone(), Vec::new()),
+        _ => pat.clone(),
+    }
+}
+
+// turns the equation `a = b` into the rule `a => b`, if that is a valid rule.
+fn to_rule<L: Language + 'static>(a: &Pattern<L>, b: &Pattern<L>) -> Option<Rewrite<L>> {
+    // a rule with a bare pattern variable as lhs would match every e-class.
+    if matches!(a, Pattern::PVar(_)) {
+        return None;
+    }
+    let lhs = restrict_pvars(a, false);
+
+    let mut lhs_scopes = Vec::new();
+    pvar_scopes(a, &mut Vec::new(), &mut lhs_scopes);
+    let mut rhs_scopes = Vec::new();
+    pvar_scopes(b, &mut Vec::new(), &mut rhs_scopes);
+    let mut conds = Vec::new();
+    for x in rhs_scopes {
+        if lhs_scopes.contains(&x) {
+            continue;
+        }
+        let c = Condition::Fresh(x.1, x.0);
+        if !conds.contains(&c) {
+            conds.push(c);
+        }
+    }
+
+    let mut name = format!("{lhs} => {b}");
+    if !conds.is_empty() {
+        let conds: Vec<String> = conds.iter().map(|c| c.to_string()).collect();
+        name = format!("{name} where {}", conds.join(", "));
+    }
+    let cond = Condition::And(conds);
+    validate_rule(&lhs, b, Some(&cond)).ok()?;
+
+    let applier = ConditionalApplier {
+        cond: cond.clone(),
+        applier: b.clone(),
+    };
+    let rw = Rewrite::from_parts(&name, CompiledPattern::new(lhs.clone()), applier);
+    Some(rw.with_term_rule(lhs, b.clone(), Some(cond)))
+}
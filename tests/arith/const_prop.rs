@@ -61,3 +61,58 @@ fn const_prop_union() {
 
     assert_eq!(eg.analysis_data(a.id), &Some(42));
 }
+
+#[test]
+fn const_prop_closure_applier() {
+    // (mul ?a ?b) = 0, if ?b is known to be 0.
+    let mul_zero: Rewrite<Arith, ConstProp> = Rewrite::from_parts(
+        "mul-zero",
+        Pattern::parse("(mul ?a ?b)").unwrap(),
+        |subst: &Subst, eg: &mut EGraph<Arith, ConstProp>| {
+            if eg.analysis_data(subst["b"].id) == &Some(0) {
+                Some(eg.add(Arith::Number(0)))
+            } else {
+                None
+            }
+        },
+    );
+
+    let mut eg = EGraph::<Arith, ConstProp>::default();
+    let i = eg.add_expr(RecExpr::parse("(mul x (add 0 0))").unwrap());
+    assert_eq!(eg.analysis_data(i.id), &None);
+
+    apply_rewrites(&mut eg, &[mul_zero]);
+    assert_eq!(eg.analysis_data(i.id), &Some(0));
+}
+
+#[test]
+fn const_prop_multi_rewrite() {
+    let rw: Rewrite<Arith, ConstProp> = Rewrite::new_multi(
+        "add-comm-multi",
+        "?a == (add ?x ?y), ?b == (add ?y ?x)",
+        "?a == ?b",
+    );
+
+    let mut eg = EGraph::<Arith, ConstProp>::default();
+    let a = eg.add_expr(RecExpr::parse("(add x y)").unwrap());
+    let b = eg.add_expr(RecExpr::parse("(add y x)").unwrap());
+    assert!(!eg.eq(&a, &b));
+
+    apply_rewrites(&mut eg, &[rw]);
+    assert!(eg.eq(&a, &b));
+}
+
+#[test]
+#[should_panic(expected = "requires a searcher with a single pattern")]
+fn multi_pattern_with_pattern_applier() {
+    let lhs = MultiPattern::parse("?a == (add ?x ?y), ?b == (add ?y ?x)").unwrap();
+    let rhs = Pattern::parse("?a").unwrap();
+    let _: Rewrite<Arith, ConstProp> = Rewrite::from_parts("bad", lhs, rhs);
+}
+
+#[test]
+#[should_panic(expected = "`?c` is not bound by the lhs")]
+fn multi_rewrite_unbound_pvar() {
+    let _: Rewrite<Arith, ConstProp> =
+        Rewrite::new_multi("bad", "?a == (add ?x ?y), ?b == (add ?y ?x)", "?c == ?b");
+}
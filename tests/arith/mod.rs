@@ -15,6 +15,9 @@ pub use my_cost::*;
 mod const_prop;
 pub use const_prop::*;
 
+mod synth;
+pub use synth::*;
+
 define_language! {
     pub enum Arith {
         // lambda calculus:
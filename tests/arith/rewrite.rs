@@ -34,7 +34,7 @@ fn eta() -> Rewrite<Arith> {
     })
 }
 
-fn eta_expansion() -> Rewrite<Arith> {
+pub fn eta_expansion() -> Rewrite<Arith> {
     let pat = "?b";
     let outpat = "(lam $1 (app ?b (var $1)))";
     Rewrite::new("eta-expansion", pat, outpat)
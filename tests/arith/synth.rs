@@ -0,0 +1,78 @@
+use crate::*;
+
+// the values of `?a` and `?b` in each sample.
+const SAMPLES: [(i64, i64); 4] = [(0, 1), (2, 3), (-5, 7), (11, -13)];
+
+fn eval(pat: &Pattern<Arith>, env: &mut HashMap<Slot, i64>, i: usize) -> Option<i64> {
+    let (a, b) = SAMPLES[i];
+    match pat {
+        Pattern::PVar(v) if v == "a" => Some(a),
+        Pattern::PVar(v) if v == "b" => Some(b),
+        Pattern::ENode(Arith::Number(n), _) => Some(*n as i64),
+        Pattern::ENode(Arith::Var(s), _) => env.get(s).copied(),
+        Pattern::ENode(Arith::Add(..), c) => {
+            Some(eval(&c[0], env, i)?.wrapping_add(eval(&c[1], env, i)?))
+        }
+        Pattern::ENode(Arith::Mul(..), c) => {
+            Some(eval(&c[0], env, i)?.wrapping_mul(eval(&c[1], env, i)?))
+        }
+        Pattern::ENode(Arith::Let(bind, _), c) => {
+            let t = eval(&c[1], env, i)?;
+            let old = env.insert(bind.slot, t);
+            let out = eval(&c[0], env, i);
+            match old {
+                Some(x) => env.insert(bind.slot, x),
+                None => env.remove(&bind.slot),
+            };
+            out
+        }
+        _ => None,
+    }
+}
+
+fn synthesizer() -> Synthesizer<Arith, i64> {
+    Synthesizer::new(Arith::Var, SAMPLES.len(), |pat, i| {
+        eval(pat, &mut HashMap::default(), i)
+    })
+    .with_atom("?a")
+    .with_atom("?b")
+    .with_atom("0")
+    .with_atom("(var $1)")
+    .with_op("(add ?0 ?1)")
+    .with_op("(let $1 ?0 ?1)")
+    .with_max_size(4)
+}
+
+#[test]
+fn synth_terms() {
+    let terms = synthesizer().with_max_size(3).terms();
+    let terms: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+    assert!(terms.contains(&"(let $1 (var $1) ?a)".to_string()));
+    assert!(terms.contains(&"(add ?a 0)".to_string()));
+    // `$1` occurs both free and bound.
+    assert!(!terms.contains(&"(add (var $1) (let $1 ?a ?b))".to_string()));
+}
+
+#[test]
+fn synth_binder_rules() {
+    let rules = synthesizer().synthesize();
+    let names: Vec<&str> = rules.iter().map(|r| r.name()).collect();
+    assert!(names.contains(&"(let $1 (var $1) ?a) => ?a"), "{names:?}");
+    assert!(names.contains(&"(let $1 ?a[] ?b) => ?a"), "{names:?}");
+    assert!(names.contains(&"(add ?a 0) => ?a"), "{names:?}");
+    // commutativity is only found once.
+    let comm = names
+        .iter()
+        .filter(|n| n.starts_with("(add ?b ?a) => ") || n.starts_with("(add ?a ?b) => "))
+        .count();
+    assert_eq!(comm, 2, "{names:?}");
+
+    assert!(equal_after(
+        &rules,
+        "(let $x (var $x) (add 0 (var $y)))",
+        "(var $y)"
+    ));
+    assert!(equal_after(&rules, "(let $x (var $y) 0)", "(var $y)"));
+    // `$x` is bound, so the let can't be dropped.
+    assert!(!equal_after(&rules, "(let $x (var $x) 1)", "(var $x)"));
+}
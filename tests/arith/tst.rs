@@ -84,3 +84,703 @@ fn t6() {
     let b = &format!("(mul (var {z}) (add (var {y}) (var {x})))");
     assert_reaches(a, b, &[add_comm()], 10);
 }
+
+#[test]
+fn backoff_bans_eta_expansion() {
+    let start = RecExpr::parse("(add (var $0) (var $1))").unwrap();
+
+    let mut rewrites = get_all_rewrites();
+    rewrites.push(eta_expansion());
+
+    let mut runner: Runner<Arith> = Runner::default()
+        .with_expr(&start)
+        .with_iter_limit(5)
+        .with_scheduler(BackoffScheduler::default().with_initial_match_limit(3));
+    runner.run(&rewrites);
+
+    let banned = |rule: &str| {
+        runner
+            .iterations
+            .iter()
+            .any(|it| it.banned_rules.iter().any(|x| x == rule))
+    };
+    assert!(banned("eta-expansion"));
+    assert!(!banned("add-comm"));
+}
+
+#[test]
+fn backoff_bans_uncounted_rules() {
+    // a custom rule that does all its work in the applier, so its matches aren't counted.
+    let explode: Rewrite<Arith> = RewriteT {
+        searcher: Box::new(|_| ()),
+        applier: Box::new(|(), eg: &mut EGraph<Arith>| {
+            let one = eg.add(Arith::Number(1));
+            for i in eg.ids() {
+                let x = eg.mk_identity_applied_id(i);
+                let y = eg.add(Arith::Mul(x.clone(), one.clone()));
+                eg.union(&x, &y);
+            }
+        }),
+    }
+    .into_named("explode");
+
+    let start = RecExpr::parse("(add (var $0) (var $1))").unwrap();
+    let mut runner: Runner<Arith> = Runner::default()
+        .with_expr(&start)
+        .with_iter_limit(3)
+        .with_scheduler(BackoffScheduler::default().with_initial_match_limit(2));
+    runner.run(&[explode]);
+
+    assert!(runner.iterations[0].banned_rules.is_empty());
+    assert_eq!(runner.iterations[1].banned_rules, ["explode"]);
+}
+
+#[test]
+#[should_panic(expected = "the rule name `comm` is used twice")]
+fn runner_rejects_duplicate_names() {
+    // otherwise, both rules would share their bans and statistics.
+    let rewrites = [
+        Rewrite::new("comm", "(add ?a ?b)", "(add ?b ?a)"),
+        Rewrite::new("comm", "(mul ?a ?b)", "(mul ?b ?a)"),
+    ];
+    let start = RecExpr::parse("(add (var $0) (var $1))").unwrap();
+    let mut runner: Runner<Arith> = Runner::default().with_expr(&start);
+    runner.run(&rewrites);
+}
+
+#[test]
+fn rule_stats() {
+    let start = RecExpr::parse("(add (var $0) (mul (var $1) 0))").unwrap();
+    let rewrites = [
+        Rewrite::new("add-comm", "(add ?a ?b)", "(add ?b ?a)"),
+        Rewrite::new("mul-zero", "(mul ?a 0)", "0"),
+        Rewrite::new("never", "(lam $1 ?a)", "?a"),
+    ];
+
+    let mut runner: Runner<Arith> = Runner::default().with_expr(&start).with_iter_limit(5);
+    let report = runner.run(&rewrites);
+
+    let first = &runner.iterations[0].rule_stats;
+    assert_eq!(first["add-comm"].matches, 1);
+    assert_eq!(first["add-comm"].unions, 1);
+    assert_eq!(first["mul-zero"].unions, 1);
+    assert_eq!(first["never"].matches, 0);
+
+    let total = &report.rule_stats;
+    assert_eq!((total["never"].matches, total["never"].unions), (0, 0));
+    assert!(total["add-comm"].matches > total["add-comm"].unions);
+    let sum = |f: fn(&RuleStats) -> usize| {
+        runner
+            .iterations
+            .iter()
+            .map(|it| f(&it.rule_stats["add-comm"]))
+            .sum::<usize>()
+    };
+    assert_eq!(total["add-comm"].matches, sum(|s| s.matches));
+    assert_eq!(total["add-comm"].unions, sum(|s| s.unions));
+
+    let mut eg: EGraph<Arith> = EGraph::default();
+    eg.add_expr(start);
+    let (changed, stats) = apply_rewrites_with_stats(&mut eg, &rewrites);
+    assert!(changed);
+    assert_eq!(stats["mul-zero"].matches, 1);
+
+    // unions of closure appliers are counted as well.
+    let lhs = Pattern::parse("(mul ?a 0)").unwrap();
+    let zero = |_: &Subst, eg: &mut EGraph<Arith>| Some(eg.add_expr(RecExpr::parse("0").unwrap()));
+    let mut eg: EGraph<Arith> = EGraph::default();
+    eg.add_expr(RecExpr::parse("(mul (var $1) 0)").unwrap());
+    let (_, stats) = apply_rewrites_with_stats(&mut eg, &[Rewrite::from_parts("zero", lhs, zero)]);
+    assert_eq!(stats["zero"].unions, 1);
+    assert_eq!(eg.union_count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "the rule name `mul-zero` is used twice")]
+fn rule_stats_duplicate_names() {
+    let rewrites = [
+        Rewrite::new("mul-zero", "(mul ?a 0)", "0"),
+        Rewrite::new("mul-zero", "(mul 0 ?a)", "0"),
+    ];
+    let mut eg: EGraph<Arith> = EGraph::default();
+    apply_rewrites_with_stats(&mut eg, &rewrites);
+}
+
+#[test]
+fn runner_phases() {
+    let start = RecExpr::parse("(add (var $0) (mul (var $1) 0))").unwrap();
+    let simplify = || {
+        vec![
+            Rewrite::new("mul-zero", "(mul ?a 0)", "0"),
+            Rewrite::new("add-zero", "(add ?a 0)", "?a"),
+        ]
+    };
+    let comm = || vec![Rewrite::new("add-comm", "(add ?a ?b)", "(add ?b ?a)")];
+
+    let mut runner: Runner<Arith> = Runner::default()
+        .with_expr(&start)
+        .with_rule_set("simplify", simplify())
+        .with_rule_set("comm", comm())
+        .with_phase("simplify")
+        .with_limited_phase("comm", 1)
+        .with_phase_repeats(3);
+    let report = runner.run_phases();
+
+    // the second round doesn't change anything, so there is no third round.
+    let phases: Vec<(&str, usize, usize)> = report
+        .phases
+        .iter()
+        .map(|p| (&p.rule_set[..], p.round, p.iterations))
+        .collect();
+    assert_eq!(
+        phases,
+        [
+            ("simplify", 0, 3),
+            ("comm", 0, 1),
+            ("simplify", 1, 1),
+            ("comm", 1, 1)
+        ]
+    );
+    assert!(matches!(
+        report.phases[0].stop_reason,
+        StopReason::Saturated
+    ));
+    assert!(matches!(
+        report.phases[1].stop_reason,
+        StopReason::PhaseLimit
+    ));
+    assert!(matches!(
+        report.phases[3].stop_reason,
+        StopReason::Saturated
+    ));
+    assert!(matches!(report.stop_reason, StopReason::Saturated));
+    assert_eq!(report.iterations, 6);
+    let var = runner.egraph.add_expr(RecExpr::parse("(var $0)").unwrap());
+    assert!(runner.egraph.eq(&runner.roots[0], &var));
+
+    // the limits of the runner stop all phases.
+    let mut runner: Runner<Arith> = Runner::default()
+        .with_expr(&start)
+        .with_iter_limit(0)
+        .with_rule_set("simplify", simplify())
+        .with_rule_set("comm", comm())
+        .with_phase("simplify")
+        .with_phase("comm");
+    let report = runner.run_phases();
+    assert_eq!(report.phases.len(), 1);
+    assert!(matches!(
+        report.phases[0].stop_reason,
+        StopReason::IterationLimit
+    ));
+    assert!(matches!(report.stop_reason, StopReason::IterationLimit));
+
+    // the last round changed the e-graph, so the runner isn't saturated, even though its last phase is.
+    let mut runner: Runner<Arith> = Runner::default()
+        .with_expr(&start)
+        .with_rule_set("simplify", simplify())
+        .with_rule_set("comm", comm())
+        .with_limited_phase("comm", 1)
+        .with_phase("simplify");
+    let report = runner.run_phases();
+    assert!(matches!(
+        report.phases[1].stop_reason,
+        StopReason::Saturated
+    ));
+    assert!(matches!(report.stop_reason, StopReason::RepeatLimit));
+
+    // the hooks see all rule sets.
+    let mut runner: Runner<Arith> = Runner::default()
+        .with_expr(&start)
+        .with_rule_set("simplify", simplify())
+        .with_rule_set("comm", comm())
+        .with_phase("simplify")
+        .with_phase("comm")
+        .with_phase_repeats(3)
+        .with_hook(|runner| match runner.rule_sets.len() {
+            2 => Ok(()),
+            n => Err(format!("{n} rule sets")),
+        });
+    let report = runner.run_phases();
+    assert!(matches!(report.stop_reason, StopReason::Saturated));
+}
+
+#[test]
+#[should_panic(expected = "the phases have to run at least once")]
+fn runner_zero_phase_repeats() {
+    let _: Runner<Arith> = Runner::default().with_phase_repeats(0);
+}
+
+#[test]
+fn iteration_data() {
+    let start = RecExpr::parse("(add (var $0) (mul (var $1) 0))").unwrap();
+    let rewrites = [
+        Rewrite::new("add-comm", "(add ?a ?b)", "(add ?b ?a)"),
+        Rewrite::new("mul-zero", "(mul ?a 0)", "0"),
+    ];
+    let mut runner: Runner<Arith> = Runner::default().with_expr(&start).with_iter_limit(5);
+    let report = runner.run(&rewrites);
+    assert_eq!(report.iterations, runner.iterations.len());
+
+    let first = &runner.iterations[0];
+    assert_eq!(first.num_unions, 2);
+    assert!(first.max_pending > 0);
+    let last = runner.iterations.last().unwrap();
+    assert_eq!(last.num_unions, 0);
+    assert!(last.progress == runner.egraph.progress());
+    assert_eq!(
+        last.progress.number_of_live_classes,
+        runner.egraph.ids().len()
+    );
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_value(&runner.iterations).unwrap();
+        assert_eq!(json[0]["num_unions"], 2);
+        assert!(json[0]["progress"]["sum_of_slots"].is_u64());
+        assert!(json[0]["rebuild_time"].is_object());
+        assert!(json[0].get("finish_time").is_none());
+
+        let json = serde_json::to_string(&report).unwrap();
+        let report: Report = serde_json::from_str(&json).unwrap();
+        assert!(matches!(report.stop_reason, StopReason::Saturated));
+    }
+}
+
+#[test]
+fn runner_goal() {
+    let start = RecExpr::parse("(add (var $0) (mul (var $1) 0))").unwrap();
+    let goal = RecExpr::parse("(var $0)").unwrap();
+    let rewrites = [
+        Rewrite::new("add-comm", "(add ?a ?b)", "(add ?b ?a)"),
+        Rewrite::new("mul-zero", "(mul ?a 0)", "0"),
+        Rewrite::new("add-zero", "(add ?a 0)", "?a"),
+    ];
+
+    let mut runner: Runner<Arith> = Runner::default().with_goal(start.clone(), goal.clone());
+    let report = runner.run(&rewrites);
+    assert!(matches!(report.stop_reason, StopReason::GoalReached));
+    assert_eq!(report.iterations, 2);
+    #[cfg(feature = "explanations")]
+    {
+        assert_eq!(report.goal_proofs.len(), 1);
+        assert!(!report.goal_proofs[0].to_string(&runner.egraph).is_empty());
+    }
+
+    // the runner only stops early, if all goals hold.
+    let zero = RecExpr::parse("0").unwrap();
+    let mut runner: Runner<Arith> =
+        Runner::default().with_goals([(start.clone(), goal), (start, zero)]);
+    let report = runner.run(&rewrites);
+    assert!(matches!(report.stop_reason, StopReason::Saturated));
+}
+
+fn file_rewrites() -> Vec<Rewrite<Arith>> {
+    load_rules(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/arith/arith.rules"
+    ))
+    .unwrap()
+}
+
+#[test]
+fn rule_file() {
+    let rewrites = file_rewrites();
+    assert_eq!(rewrites.len(), 14);
+    assert!(rewrites.iter().any(|rw| rw.name() == "distr-rev"));
+
+    // (x+y)**2 = x**2 + x*y + x*y + y**2
+    let a = "(mul (add (var $x) (var $y)) (add (var $x) (var $y)))";
+    let b = "(add (mul (var $x) (var $x))
+             (add (mul (var $x) (var $y))
+             (add (mul (var $x) (var $y))
+                  (mul (var $y) (var $y))
+             )))";
+    assert_reaches(a, b, &rewrites[..], 10);
+}
+
+#[test]
+fn rule_file_errors() {
+    let line_of = |s: &str| match parse_rules::<Arith, ()>(s) {
+        Err(RuleParseError::Line(line, _)) => line,
+        _ => panic!("expected an error"),
+    };
+
+    assert_eq!(
+        line_of("a: (add ?a ?b) => (add ?b ?a)\n(add ?a ?b) => ?a"),
+        2
+    );
+    assert_eq!(line_of("\n\na: (add ?a ?b) ?a"), 3);
+    assert_eq!(line_of("a: (add ?a ?b => ?a"), 1);
+    assert_eq!(line_of("a: ?a => ?a if (slot_free_in ?a $1)"), 1);
+    assert_eq!(
+        line_of("// ok\nbeta: (app (lam $1 ?b) ?t) <=> ?b[(var $1) := ?t]"),
+        2
+    );
+
+    let message = |s: &str| parse_rules::<Arith, ()>(s).err().unwrap().to_string();
+    assert_eq!(
+        message("\n\n(add ?a ?b) => ?a"),
+        "line 3: missing rule name"
+    );
+    assert_eq!(
+        message("a: (add ?a ?b) ?a"),
+        "line 1: missing `=>` or `<=>`"
+    );
+    assert_eq!(
+        message("a: (add ?a ?b) => ?c"),
+        "line 1: `?c` is not bound by the lhs"
+    );
+}
+
+#[test]
+fn incremental_ematch() {
+    let patterns = ["(add ?a ?b)", "(mul (add ?a ?b) ?c)", "(add ?a ?a)", "?x"];
+    let start = "(mul (add (var $x) (var $y)) (add (var $x) (var $y)))";
+    assert_incremental_ematch(start, &patterns, &get_all_rewrites(), 4);
+}
+
+#[test]
+fn compiled_pattern_repeated_pvars() {
+    let mut eg: EGraph<Arith> = EGraph::default();
+    let xx = eg.add_expr(RecExpr::parse("(add (var $x) (var $x))").unwrap());
+    eg.add_expr(RecExpr::parse("(add (var $x) (var $y))").unwrap());
+
+    let pat = CompiledPattern::new(Pattern::parse("(add ?a ?a)").unwrap());
+    let matches: Vec<Subst> = Searcher::<Arith, ()>::search(&pat, &eg);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(pat.run(&eg, xx.id).len(), 1);
+
+    let pat = CompiledPattern::new(Pattern::parse("(add (var $1) (var $2))").unwrap());
+    assert_eq!(Searcher::<Arith, ()>::search(&pat, &eg).len(), 1);
+}
+
+#[test]
+fn operator_index() {
+    let mut eg: EGraph<Arith> = EGraph::default();
+    let a = eg.add_expr(RecExpr::parse("(add (var $x) (mul (var $y) (var $x)))").unwrap());
+    let m = eg.add_expr(RecExpr::parse("(mul (var $y) (var $x))").unwrap());
+
+    let add = Pattern::parse("(add ?a ?b)").unwrap();
+    let Pattern::ENode(add, _) = add else {
+        panic!()
+    };
+    assert_eq!(eg.classes_with_operator(&add), vec![a.id]);
+
+    // after the union, the `mul` e-node lives in the class of the `add` e-node.
+    eg.union(&a, &m);
+    let mul = Pattern::parse("(mul ?a ?b)").unwrap();
+    let Pattern::ENode(mul, _) = mul else {
+        panic!()
+    };
+    assert_eq!(
+        eg.classes_with_operator(&mul),
+        vec![eg.find_applied_id(&a).id]
+    );
+    assert_eq!(
+        eg.classes_with_operator(&add),
+        vec![eg.find_applied_id(&a).id]
+    );
+    eg.check();
+}
+
+#[test]
+fn operator_variable() {
+    let comm: Rewrite<Arith> = Rewrite::new_if("comm", "(?op ?a ?b)", "(?op ?b ?a)", |subst, _| {
+        matches!(
+            subst.operator::<Arith>("op"),
+            Some(Arith::Add(..) | Arith::Mul(..))
+        )
+    });
+    let comm = &[comm];
+
+    assert!(equal_after(
+        comm,
+        "(add (var $x) (var $y))",
+        "(add (var $y) (var $x))"
+    ));
+    assert!(equal_after(
+        comm,
+        "(mul (var $x) (var $y))",
+        "(mul (var $y) (var $x))"
+    ));
+    assert!(!equal_after(
+        comm,
+        "(add (var $x) (var $y))",
+        "(mul (var $y) (var $x))"
+    ));
+    assert!(!equal_after(
+        comm,
+        "(app (var $x) (var $y))",
+        "(app (var $y) (var $x))"
+    ));
+}
+
+#[test]
+fn operator_variable_with_slots() {
+    let add_zero: [Rewrite<Arith>; 2] = [
+        Rewrite::new("unary", "(?op (add ?a 0))", "(?op ?a)"),
+        Rewrite::new("binary", "(?op ?a (add ?b 0))", "(?op ?a ?b)"),
+    ];
+
+    assert!(equal_after(
+        &add_zero,
+        "(lam $x (add (var $x) 0))",
+        "(lam $x (var $x))"
+    ));
+    assert!(equal_after(
+        &add_zero,
+        "(let $x (var $y) (add (var $x) 0))",
+        "(let $x (var $y) (var $x))"
+    ));
+    assert!(!equal_after(
+        &add_zero,
+        "(lam $x (add (var $x) 0))",
+        "(lam $x (var $y))"
+    ));
+
+    let subst: Subst = HashMap::default().into();
+    assert!(subst.is_empty());
+}
+
+#[test]
+fn wildcard_and_or_pattern() {
+    let rewrites: [Rewrite<Arith>; 2] = [
+        Rewrite::new("mul-zero", "(mul 0 _)", "0"),
+        Rewrite::new("add-zero", "(| (add ?a 0) (add 0 ?a))", "?a"),
+    ];
+
+    assert!(equal_after(&rewrites, "(mul 0 (var $x))", "0"));
+    assert!(equal_after(&rewrites, "(add (var $x) 0)", "(var $x)"));
+    assert!(equal_after(&rewrites, "(add 0 (var $x))", "(var $x)"));
+    assert!(!equal_after(
+        &rewrites,
+        "(add (var $x) (var $y))",
+        "(var $x)"
+    ));
+}
+
+#[test]
+fn pattern_extensions_display() {
+    for s in ["(| (add ?a _) (?op ?a ?b))", "(?op)", "_"] {
+        let pat: Pattern<Arith> = Pattern::parse(s).unwrap();
+        assert_eq!(pat.to_string(), s);
+    }
+}
+
+#[test]
+fn where_conditions() {
+    let eta = || Rewrite::<Arith>::new("eta", "(lam $1 (app ?f (var $1)))", "?f where $1 # ?f");
+    assert!(equal_after(
+        &[eta()],
+        "(lam $x (app (var $y) (var $x)))",
+        "(var $y)"
+    ));
+    assert!(!equal_after(
+        &[eta()],
+        "(lam $x (app (var $x) (var $x)))",
+        "(var $x)"
+    ));
+
+    for s in ["$1 # ?f", "(not (or $1 # ?a (and $2 # ?b $1 # ?b)))"] {
+        assert_eq!(Condition::parse(s).unwrap().to_string(), s);
+    }
+    assert_eq!(
+        Condition::parse("$1 # ?a, (slot_free_in $2 ?b)").unwrap(),
+        Condition::And(vec![
+            Condition::Fresh(Slot::named("1"), "a".to_string()),
+            Condition::Fresh(Slot::named("2"), "b".to_string()),
+        ])
+    );
+    assert!(Condition::parse("?a # $1").is_err());
+}
+
+#[test]
+fn cond_combinators() {
+    let double: Rewrite<Arith> =
+        Rewrite::new_cond("double", "(add ?a ?b)", "(mul 2 ?a)", equal("a", "b"));
+    assert!(equal_after(
+        &[double],
+        "(add (var $x) (var $x))",
+        "(mul 2 (var $x))"
+    ));
+    let double: Rewrite<Arith> =
+        Rewrite::new_cond("double", "(add ?a ?b)", "(mul 2 ?a)", equal("a", "b"));
+    assert!(!equal_after(
+        &[double],
+        "(add (var $x) (var $y))",
+        "(mul 2 (var $x))"
+    ));
+
+    let mul_zero =
+        || -> Rewrite<Arith> { rw!("mul-zero"; "(mul ?a ?b)" => "0", if contains_match("a", "0")) };
+    assert!(equal_after(&[mul_zero()], "(mul 0 (var $x))", "0"));
+    assert!(!equal_after(&[mul_zero()], "(mul 1 (var $x))", "0"));
+
+    let comm =
+        || -> Rewrite<Arith> { rw!("comm"; "(add ?a ?b)" => "(add ?b ?a)", if max_slots("a", 0)) };
+    assert!(equal_after(
+        &[comm()],
+        "(add 1 (var $x))",
+        "(add (var $x) 1)"
+    ));
+    assert!(!equal_after(
+        &[comm()],
+        "(add (var $x) (var $y))",
+        "(add (var $y) (var $x))"
+    ));
+
+    let let_unused = || -> Rewrite<Arith> {
+        rw!("let-unused"; "(let $1 ?b ?e)" => "?b", if !slot_occurs_in("1", "b"))
+    };
+    assert!(equal_after(
+        &[let_unused()],
+        "(let $x (var $y) 1)",
+        "(var $y)"
+    ));
+    assert!(!equal_after(
+        &[let_unused()],
+        "(let $x (var $x) 1)",
+        "(var $x)"
+    ));
+
+    let add_zero = |a: &str, b: &str| {
+        let is_zero = analysis("a", "is_zero", |d: &Option<u32>| *d == Some(0));
+        let rw: Rewrite<Arith, ConstProp> =
+            Rewrite::new_cond("add-zero", "(add ?a ?b)", "?b", is_zero);
+        let mut eg: EGraph<Arith, ConstProp> = EGraph::default();
+        let a = eg.add_expr(RecExpr::parse(a).unwrap());
+        let b = eg.add_expr(RecExpr::parse(b).unwrap());
+        apply_rewrites(&mut eg, &[rw]);
+        eg.eq(&a, &b)
+    };
+    assert!(add_zero("(add (mul 0 2) (var $x))", "(var $x)"));
+    assert!(!add_zero("(add (mul 1 2) (var $x))", "(var $x)"));
+
+    // `slot_free_in`, `and`, `or` and `not` are closures.
+    let eta: Rewrite<Arith> = Rewrite::new_if(
+        "eta",
+        "(lam $1 (app ?f (var $1)))",
+        "?f",
+        slot_free_in("1", "f"),
+    );
+    assert!(equal_after(
+        &[eta],
+        "(lam $x (app (var $y) (var $x)))",
+        "(var $y)"
+    ));
+    let c = and(
+        equal("a", "b"),
+        not(or(slot_occurs_in("1", "a"), max_slots("a", 0))),
+    );
+    let rw: Rewrite<Arith> = Rewrite::new_if("double", "(add ?a ?b)", "(mul 2 ?a)", c);
+    assert!(equal_after(
+        &[rw],
+        "(add (var $x) (var $x))",
+        "(mul 2 (var $x))"
+    ));
+
+    assert_eq!(equal("a", "b").to_string(), "?a = ?b");
+    assert_eq!(slot_occurs_in("1", "a").to_string(), "(not $1 # ?a)");
+    assert_eq!(
+        contains_match::<Arith>("a", "(add ?x 0)").to_string(),
+        "?a contains (add ?x 0)"
+    );
+    assert_eq!(max_slots("a", 1).to_string(), "|slots(?a)| <= 1");
+    let is_zero = analysis("a", "is_zero", |d: &Option<u32>| *d == Some(0));
+    assert_eq!(is_zero.to_string(), "is_zero(?a)");
+}
+
+#[test]
+fn try_new_validation() {
+    let check = |a: &str, b: &str| Rewrite::<Arith>::try_new("r", a, b).err();
+
+    assert!(check("(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]").is_none());
+    assert!(check("?f", "(lam $1 (app ?f (var $1)))").is_none());
+    assert!(check("(lam $1 (app ?f (var $1)))", "?f where $1 # ?f").is_none());
+    assert!(check("(| (add ?a 0) (add 0 ?a))", "?a").is_none());
+
+    assert!(matches!(
+        check("(add ?a", "?a"),
+        Some(RewriteError::Parse(_))
+    ));
+    assert!(matches!(
+        check("?b[(var $1) := ?t]", "?b"),
+        Some(RewriteError::SubstInLhs(_))
+    ));
+    assert!(matches!(
+        check("?a", "(add ?a _)"),
+        Some(RewriteError::UnsupportedInRhs(_))
+    ));
+    assert!(matches!(
+        check("(add ?a ?b)", "?c"),
+        Some(RewriteError::UnboundPVar(v)) if v == "c"
+    ));
+    assert!(matches!(
+        check("(| (add ?a 0) (mul ?b 0))", "?a"),
+        Some(RewriteError::UnboundPVar(v)) if v == "a"
+    ));
+    assert!(matches!(
+        check("?a", "?a where $1 # ?b"),
+        Some(RewriteError::UnboundPVar(v)) if v == "b"
+    ));
+    assert!(matches!(
+        check("(lam $1 ?b)", "?b[?b]"),
+        Some(RewriteError::NotHigherOrder(f)) if f == "b"
+    ));
+    assert!(matches!(
+        check("?a", "(add ?a (var $1))"),
+        Some(RewriteError::UnboundSlot(s)) if s == Slot::numeric(1)
+    ));
+    assert!(matches!(
+        check("(app (lam $1 ?b) (var $1))", "?b"),
+        Some(RewriteError::BoundAndFree(s)) if s == Slot::numeric(1)
+    ));
+    assert!(matches!(
+        check("(lam $1 (lam $1 ?b))", "?b"),
+        Some(RewriteError::BoundTwice(s)) if s == Slot::numeric(1)
+    ));
+    assert!(matches!(
+        check("(lam $1 ?b[$1])", "(lam $1 ?b[$1, $1])"),
+        Some(RewriteError::HOArity(_))
+    ));
+    assert!(matches!(
+        check("(lam $1 ?b[$1])", "(lam $1 ?b[(var $1)])"),
+        Some(RewriteError::HOArgKind(_))
+    ));
+    assert!(matches!(
+        check("(?op ?a ?b)", "(?op ?a)"),
+        Some(RewriteError::OpArity(op)) if op == "op"
+    ));
+    assert_eq!(
+        check("(add ?a ?b)", "?c").unwrap().to_string(),
+        "`?c` is not bound by the lhs"
+    );
+
+    let multi = |a: &str, b: &str| Rewrite::<Arith>::try_new_multi("r", a, b).err();
+    assert!(multi("?a == (add ?x ?y), ?b == (add ?y ?x)", "?a == ?b").is_none());
+    assert!(matches!(
+        multi("?a == (add ?x ?y)", "?a == ?z"),
+        Some(RewriteError::UnboundPVar(v)) if v == "z"
+    ));
+    assert!(matches!(
+        multi("?a == (add ?x ?y)", "?a == (add ?x _)"),
+        Some(RewriteError::UnsupportedInRhs(_))
+    ));
+    assert!(matches!(
+        multi("?a == (add ?x ?y)", "?a == (var $1)"),
+        Some(RewriteError::UnboundSlot(_))
+    ));
+
+    let lhs = Pattern::parse("(add ?a ?b)").unwrap();
+    let rhs = Pattern::parse("(?op ?a)").unwrap();
+    assert!(matches!(
+        Rewrite::<Arith>::try_from_parts("r", lhs, rhs).err(),
+        Some(RewriteError::UnboundPVar(v)) if v == "op"
+    ));
+
+    let rules = parse_rules::<Arith, ()>("bad: (add ?a ?b) => ?c");
+    assert!(matches!(
+        rules,
+        Err(RuleParseError::Line(1, RuleLineError::Invalid(_)))
+    ));
+}
@@ -148,3 +148,51 @@ fn multipat_test8() {
     dbg!(&matches);
     assert_eq!(matches.len(), 1);
 }
+
+#[test]
+fn multipat_test9() {
+    let mut eg: EGraph<Arith2> = EGraph::new(());
+
+    eg.add_expr(RecExpr::parse("(f (var $x) (sub (var $y) (var $y)))").unwrap());
+
+    // nested patterns are flattened.
+    let pat: MultiPattern<Arith2> = MultiPattern::parse("?out == (f ?a (sub ?b ?b)), ?b == (var $y)").unwrap();
+    let matches = multi_ematch(&pat, &eg);
+    assert_eq!(matches.len(), 1);
+}
+
+#[test]
+fn multipat_test10() {
+    let mut eg: EGraph<Arith2> = EGraph::new(());
+
+    let a = eg.add_expr(RecExpr::parse("(sub (var $x) (var $x))").unwrap());
+    eg.add_expr(RecExpr::parse("zero").unwrap());
+
+    let pat: MultiPattern<Arith2> = MultiPattern::parse("?a == (sub ?x ?x), ?b == zero, ?a == ?b").unwrap();
+    assert!(multi_ematch(&pat, &eg).is_empty());
+
+    let b = eg.add_expr(RecExpr::parse("zero").unwrap());
+    eg.union(&a, &b);
+
+    let matches = multi_ematch(&pat, &eg);
+    assert_eq!(matches.len(), 1);
+    assert!(eg.eq(&matches[0]["a"], &matches[0]["b"]));
+}
+
+#[test]
+fn multipat_parse_errors() {
+    let parse = |s: &str| MultiPattern::<Arith2>::parse(s);
+
+    assert!(matches!(parse("?a (f ?b ?c)"), Err(ParseError::ExpectedEquation(_))));
+    assert!(matches!(parse("?a == ?b == ?c"), Err(ParseError::ExpectedEquation(_))));
+    assert!(matches!(parse("zero == (f ?a ?b)"), Err(ParseError::ExpectedPVar(_))));
+    assert!(matches!(parse("?a == ?b[(var $x) := zero]"), Err(ParseError::SubstInMultiPattern(_))));
+    assert!(parse("?a == (f ?b").is_err());
+}
+
+#[test]
+fn pattern_macro_nullary() {
+    let a: Pattern<Arith2> = pattern!(Arith2, "(f zero (sub ?x zero))");
+    let b = Pattern::parse("(f zero (sub ?x zero))").unwrap();
+    assert!(a == b, "{a} != {b}");
+}
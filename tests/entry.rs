@@ -65,6 +65,77 @@ pub fn explain<L: Language>(s1: &str, s2: &str, eg: &mut EGraph<L>) {
     eg.check();
 }
 
+// checks whether `a` and `b` are equal after applying the `rewrites` once.
+pub fn equal_after<L: Language, N: Analysis<L> + Default>(
+    rewrites: &[Rewrite<L, N>],
+    a: &str,
+    b: &str,
+) -> bool {
+    equal_after_in(&mut EGraph::default(), rewrites, a, b)
+}
+
+// like `equal_after`, but in the given e-graph, e.g. one with another substitution method.
+pub fn equal_after_in<L: Language, N: Analysis<L>>(
+    eg: &mut EGraph<L, N>,
+    rewrites: &[Rewrite<L, N>],
+    a: &str,
+    b: &str,
+) -> bool {
+    let a = eg.add_expr(RecExpr::parse(a).unwrap());
+    let b = eg.add_expr(RecExpr::parse(b).unwrap());
+    apply_rewrites(eg, rewrites);
+    eg.eq(&a, &b)
+}
+
+// checks that an `IncrementalPattern` finds exactly the matches of a full search, while the `rewrites` are applied `steps` times.
+// Each search chooses new fresh slots, so the slots of each match are renamed to `$0, $1, ...` in order of occurrence.
+pub fn assert_incremental_ematch<L: Language, N: Analysis<L> + Default>(
+    start: &str,
+    patterns: &[&str],
+    rewrites: &[Rewrite<L, N>],
+    steps: usize,
+) {
+    let sorted = |substs: Vec<Subst>| {
+        let mut out: Vec<Vec<(String, AppliedId)>> = substs
+            .into_iter()
+            .map(|subst| {
+                let mut v: Vec<(String, AppliedId)> = HashMap::from(subst).into_iter().collect();
+                v.sort_by(|(x, _), (y, _)| x.cmp(y));
+                let mut m = SlotMap::new();
+                for s in v.iter().flat_map(|(_, x)| x.m.values_vec()) {
+                    if !m.contains_key(s) {
+                        m.insert(s, Slot::numeric(m.len() as u32));
+                    }
+                }
+                v.into_iter()
+                    .map(|(k, x)| (k, x.apply_slotmap(&m)))
+                    .collect()
+            })
+            .collect();
+        out.sort();
+        out
+    };
+
+    let patterns: Vec<Pattern<L>> = patterns
+        .iter()
+        .map(|p| Pattern::parse(p).unwrap())
+        .collect();
+    let incremental: Vec<IncrementalPattern<L>> = patterns
+        .iter()
+        .cloned()
+        .map(IncrementalPattern::new)
+        .collect();
+
+    let mut eg: EGraph<L, N> = EGraph::default();
+    eg.add_expr(RecExpr::parse(start).unwrap());
+    for _ in 0..steps {
+        for (p, ip) in patterns.iter().zip(&incremental) {
+            assert_eq!(sorted(ematch_all(&eg, p)), sorted(ip.search(&eg)));
+        }
+        apply_rewrites(&mut eg, rewrites);
+    }
+}
+
 #[derive(Clone, Debug)]
 enum ReachError {
     Reached,
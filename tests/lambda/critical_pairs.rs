@@ -0,0 +1,80 @@
+use crate::*;
+
+fn pat(s: &str) -> Pattern<Lambda> {
+    Pattern::parse(s).unwrap()
+}
+
+fn var(u: &Unifier<Lambda>, v: &str) -> String {
+    u.vars[v].to_string()
+}
+
+#[test]
+fn unify_first_order_patterns() {
+    let u = unify(&pat("(app ?a (var $1))"), &pat("(app (var $2) ?b)")).unwrap();
+    assert_eq!(var(&u, "a"), "(var $2)");
+    assert_eq!(var(&u, "b"), "(var $1)");
+    assert!(u.slots.is_empty());
+
+    // occurs check.
+    assert!(unify(&pat("?a"), &pat("(app ?a ?b)")).is_none());
+    assert!(unify(&pat("(var $1)"), &pat("(lam $2 ?x)")).is_none());
+
+    // unsupported patterns don't unify.
+    assert!(unify(&pat("(app _ ?a)"), &pat("(app ?b ?c)")).is_none());
+    assert!(unify(&pat("(| (var $1) ?a)"), &pat("?b")).is_none());
+    assert!(unify(&pat("(?op ?a ?b)"), &pat("(app ?c ?d)")).is_none());
+    assert!(unify(&pat("?a"), &pat("?b[(var $1) := ?c]")).is_none());
+}
+
+#[test]
+fn unify_modulo_binders() {
+    let u = unify(
+        &pat("(lam $1 (app ?f (var $1)))"),
+        &pat("(lam $2 (app (var $3) ?x))"),
+    )
+    .unwrap();
+    assert_eq!(u.slots[&Slot::named("2")], Slot::named("1"));
+    assert_eq!(var(&u, "f"), "(var $3)");
+    assert_eq!(var(&u, "x"), "(var $1)");
+
+    // `?f[]` may not use the binder `$1`.
+    let eta = pat("(lam $1 (app ?f[] (var $1)))");
+    assert!(unify(&eta, &pat("(lam $2 (app (var $2) ?x))")).is_none());
+    let u = unify(&eta, &pat("(lam $2 (app (lam $3 ?y) ?x))")).unwrap();
+    assert_eq!(u.fresh, vec![(Slot::named("1"), "y".to_string())]);
+
+    // distinct slots stay distinct.
+    assert!(unify(&pat("(lam $1 (var $3))"), &pat("(lam $2 (var $2))")).is_none());
+}
+
+#[test]
+fn critical_pairs_eta_beta() {
+    let eta = Rewrite::<Lambda>::new("eta", "(lam $1 (app ?f (var $1)))", "?f where $1 # ?f");
+    let beta = Rewrite::<Lambda>::new("beta", "(app (lam $1 ?b) ?t)", "(let $1 ?b ?t)");
+
+    let cps = critical_pairs(&eta, &beta);
+    assert_eq!(cps.len(), 1);
+    let cp = &cps[0];
+    assert_eq!(
+        cp.overlap.to_string(),
+        "(lam $1 (app (lam $1' ?b') (var $1)))"
+    );
+    assert_eq!(cp.position, vec![0]);
+    assert_eq!(cp.left.to_string(), "(lam $1' ?b')");
+    assert_eq!(cp.right.to_string(), "(lam $1 (let $1' ?b' (var $1)))");
+    assert_eq!(cp.fresh, vec![(Slot::named("1"), "b'".to_string())]);
+
+    let cps = critical_pairs(&beta, &eta);
+    assert_eq!(cps.len(), 1);
+    let cp = &cps[0];
+    assert_eq!(
+        cp.overlap.to_string(),
+        "(app (lam $1 (app ?f' (var $1))) ?t)"
+    );
+    assert_eq!(cp.left.to_string(), "(let $1 (app ?f' (var $1)) ?t)");
+    assert_eq!(cp.right.to_string(), "(app ?f' ?t)");
+    assert_eq!(cp.fresh, vec![(Slot::named("1"), "f'".to_string())]);
+
+    // beta doesn't overlap with itself, except trivially at the root.
+    assert!(critical_pairs(&beta, &beta).is_empty());
+}
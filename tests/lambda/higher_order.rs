@@ -0,0 +1,57 @@
+use crate::*;
+
+#[test]
+fn ho_eta() {
+    let eta = || Rewrite::<Lambda>::new("eta", "(lam $1 (app ?f[] (var $1)))", "?f");
+
+    assert!(equal_after(
+        &[eta()],
+        "(lam $x (app (var $y) (var $x)))",
+        "(var $y)"
+    ));
+    assert!(!equal_after(
+        &[eta()],
+        "(lam $x (app (var $x) (var $x)))",
+        "(var $x)"
+    ));
+}
+
+#[test]
+fn ho_beta() {
+    let beta: Rewrite<Lambda> = Rewrite::new("beta", "(app (lam $1 ?b[(var $1)]) ?t)", "?b[?t]");
+
+    assert!(equal_after(
+        &[beta],
+        "(app (lam $x (app (var $x) (var $x))) (lam $z (var $z)))",
+        "(app (lam $z (var $z)) (lam $z (var $z)))"
+    ));
+}
+
+#[test]
+fn ho_rename() {
+    let beta_var: Rewrite<Lambda> =
+        Rewrite::new("beta-var", "(app (lam $1 ?b[$1]) (var $2))", "?b[$2]");
+
+    assert!(equal_after(
+        &[beta_var],
+        "(app (lam $x (app (var $x) (var $x))) (var $y))",
+        "(app (var $y) (var $y))"
+    ));
+}
+
+#[test]
+fn ho_parse() {
+    for s in [
+        "?f[$1, (var $2)]",
+        "?f[]",
+        "?f[$1][(var $1) := ?t]",
+        "?b[(var $1) := ?t, (var $2) := ?u]",
+    ] {
+        let pat: Pattern<Lambda> = Pattern::parse(s).unwrap();
+        assert_eq!(pat.to_string(), s);
+    }
+    assert!(matches!(
+        Pattern::<Lambda>::parse("(var $1)[$1]"),
+        Err(ParseError::ArgsWithoutPVar(_))
+    ));
+}
@@ -0,0 +1,13 @@
+use crate::*;
+
+#[test]
+fn incremental_ematch_binders() {
+    let patterns = [
+        "(app (lam $1 ?b) ?t)",
+        "(lam $1 (app ?f (var $1)))",
+        "(lam $1 ?b)",
+        "(let $1 ?t (var $1))",
+    ];
+    let start = "(app (lam $x (app (var $x) (var $y))) (lam $z (app (var $z) (var $z))))";
+    assert_incremental_ematch(start, &patterns, &rewrite_small_step(), 5);
+}
@@ -0,0 +1,38 @@
+use crate::*;
+
+pub struct LambdaRealGeneratedLet;
+
+impl Realization for LambdaRealGeneratedLet {
+    fn get_rewrites() -> Vec<Rewrite<Lambda>> {
+        let mut rewrites = vec![Rewrite::new(
+            "beta",
+            "(app (lam $1 ?b) ?t)",
+            "(let $1 ?b ?t)",
+        )];
+        rewrites.extend(explicit_subst_rules("(var $1)", "(let $1 ?b ?t)").unwrap());
+        rewrites
+    }
+}
+
+unpack_tests!(LambdaRealGeneratedLet);
+
+#[test]
+fn explicit_subst_rules_errors() {
+    let rules = |var: &str, let_: &str| explicit_subst_rules::<Lambda, ()>(var, let_).err();
+    assert!(matches!(
+        rules("(var $1)", "(let $2 ?b ?t)"),
+        Some(RewriteError::InvalidLetNode(_))
+    ));
+    assert!(matches!(
+        rules("(var $1)", "(let $1 ?b (var $1))"),
+        Some(RewriteError::InvalidLetNode(_))
+    ));
+    assert!(matches!(
+        rules("(lam $1 ?b)", "(let $1 ?b ?t)"),
+        Some(RewriteError::InvalidVarNode(_))
+    ));
+    assert!(matches!(
+        rules("(var $1", "(let $1 ?b ?t)"),
+        Some(RewriteError::Parse(_))
+    ));
+}
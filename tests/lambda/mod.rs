@@ -8,6 +8,10 @@ pub use my_cost::*;
 
 mod tst;
 
+mod higher_order;
+
+mod subst_method;
+
 mod normalize;
 pub use normalize::*;
 
@@ -29,6 +33,15 @@ pub use lambda_small_step::*;
 mod let_small_step;
 pub use let_small_step::*;
 
+mod let_generated;
+pub use let_generated::*;
+
+mod term_rewrite;
+
+mod critical_pairs;
+
+mod incremental;
+
 mod native;
 pub use native::*;
 
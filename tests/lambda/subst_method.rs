@@ -0,0 +1,210 @@
+use crate::*;
+
+fn multi_subst<S: SubstMethod<Lambda, ()>>() {
+    let swap = || {
+        Rewrite::new(
+            "swap",
+            "(lam $1 (lam $2 ?b))",
+            "(lam $1 (lam $2 ?b[(var $1) := (var $2), (var $2) := (var $1)]))",
+        )
+    };
+    assert!(equal_after_in(
+        &mut EGraph::with_subst_method::<S>(()),
+        &[swap()],
+        "(lam $x (lam $y (app (var $x) (var $y))))",
+        "(lam $x (lam $y (app (var $y) (var $x))))"
+    ));
+
+    let seq = Rewrite::new(
+        "seq",
+        "(lam $1 (lam $2 ?b))",
+        "(lam $1 (lam $2 ?b[(var $1) := (var $2)][(var $2) := (var $1)]))",
+    );
+    assert!(equal_after_in(
+        &mut EGraph::with_subst_method::<S>(()),
+        &[seq],
+        "(lam $x (lam $y (app (var $x) (var $y))))",
+        "(lam $x (lam $y (app (var $x) (var $x))))"
+    ));
+
+    // the free `$y` of the argument must not be captured by the inner `lam $y`.
+    let beta = Rewrite::new("beta", "(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]");
+    assert!(equal_after_in(
+        &mut EGraph::with_subst_method::<S>(()),
+        &[beta],
+        "(app (lam $x (lam $y (app (var $x) (var $y)))) (var $y))",
+        "(lam $z (app (var $y) (var $z)))"
+    ));
+}
+
+#[test]
+fn multi_subst_syn_expr() {
+    multi_subst::<SynExprSubst>();
+}
+
+#[test]
+fn multi_subst_extraction() {
+    multi_subst::<ExtractionSubst>();
+}
+
+// A [SubstMethod] that only implements single substitutions, and uses the default `subst_many`.
+struct SingleSubst;
+
+impl SubstMethod<Lambda, ()> for SingleSubst {
+    fn new_boxed() -> Box<dyn SubstMethod<Lambda, ()>> {
+        Box::new(SingleSubst)
+    }
+
+    fn subst(
+        &mut self,
+        b: AppliedId,
+        x: AppliedId,
+        t: AppliedId,
+        eg: &mut EGraph<Lambda>,
+    ) -> AppliedId {
+        SynExprSubst.subst(b, x, t, eg)
+    }
+}
+
+#[test]
+fn multi_subst_default() {
+    multi_subst::<SingleSubst>();
+}
+
+// A [SubstMethod] that must not be used.
+struct NoSubst;
+
+impl SubstMethod<Lambda, ()> for NoSubst {
+    fn new_boxed() -> Box<dyn SubstMethod<Lambda, ()>> {
+        Box::new(NoSubst)
+    }
+
+    fn subst(
+        &mut self,
+        _: AppliedId,
+        _: AppliedId,
+        _: AppliedId,
+        _: &mut EGraph<Lambda>,
+    ) -> AppliedId {
+        panic!("variable-for-variable substitutions should be done by renaming")
+    }
+}
+
+#[test]
+fn var_renaming_subst() {
+    let check = |rw: Rewrite<Lambda>, a: &str, b: &str| {
+        let mut eg: EGraph<Lambda> = EGraph::with_subst_method::<NoSubst>(());
+        eg.set_var_node(Lambda::Var);
+        let a = eg.add_expr(RecExpr::parse(a).unwrap());
+        let b = eg.add_expr(RecExpr::parse(b).unwrap());
+        apply_rewrites(&mut eg, &[rw]);
+        assert!(eg.eq(&a, &b));
+    };
+
+    let beta_var = Rewrite::new(
+        "beta-var",
+        "(app (lam $1 ?b) (var $2))",
+        "?b[(var $1) := (var $2)]",
+    );
+    check(
+        beta_var,
+        "(app (lam $x (lam $z (app (var $x) (var $z)))) (var $y))",
+        "(lam $z (app (var $y) (var $z)))",
+    );
+
+    let swap = Rewrite::new(
+        "swap",
+        "(lam $1 (lam $2 ?b))",
+        "(lam $1 (lam $2 ?b[(var $1) := (var $2), (var $2) := (var $1)]))",
+    );
+    check(
+        swap,
+        "(lam $x (lam $y (app (var $x) (var $y))))",
+        "(lam $x (lam $y (app (var $y) (var $x))))",
+    );
+
+    // renaming would identify `$x` and `$y`, so the substitution method is used.
+    let mut eg: EGraph<Lambda> = EGraph::default();
+    eg.set_var_node(Lambda::Var);
+    let a = eg.add_expr(RecExpr::parse("(app (lam $x (app (var $x) (var $y))) (var $y))").unwrap());
+    let b = eg.add_expr(RecExpr::parse("(app (var $y) (var $y))").unwrap());
+    let beta_var = Rewrite::new(
+        "beta-var",
+        "(app (lam $1 ?b) (var $2))",
+        "?b[(var $1) := (var $2)]",
+    );
+    apply_rewrites(&mut eg, &[beta_var]);
+    assert!(eg.eq(&a, &b));
+}
+
+define_language! {
+    enum Named {
+        Lam(Bind<AppliedId>) = "lam",
+        App(AppliedId, AppliedId) = "app",
+        Var(Slot) = "var",
+        Name(Slot) = "name",
+    }
+}
+
+#[test]
+fn var_renaming_lookup() {
+    // `(name $x)` has a single slot, but isn't a variable; checking that must not add `(var $x)`.
+    let mut eg: EGraph<Named> = EGraph::default();
+    eg.set_var_node(Named::Var);
+    let a = eg.add_expr(RecExpr::parse("(app (lam $x (name $x)) (name $y))").unwrap());
+    let b = eg.add_expr(RecExpr::parse("(name $y)").unwrap());
+    let nodes = eg.total_number_of_nodes();
+    let beta = Rewrite::new("beta", "(app (lam $1 ?b) ?t)", "?b[(name $1) := ?t]");
+    apply_rewrites(&mut eg, &[beta]);
+    assert!(eg.eq(&a, &b));
+    assert_eq!(eg.total_number_of_nodes(), nodes);
+}
+
+#[test]
+fn memo_subst() {
+    let mut eg: EGraph<Lambda> = EGraph::with_subst_method::<MemoSubst<SynExprSubst>>(());
+    let mut add = |s: &str| eg.add_expr(RecExpr::parse(s).unwrap());
+    let a1 = add("(app (lam $x (app (var $x) (var $y))) (lam $z (var $z)))");
+    let a2 = add("(app (lam $u (app (var $u) (var $v))) (lam $z (var $z)))");
+    let b1 = add("(app (lam $z (var $z)) (var $y))");
+    let b2 = add("(app (lam $z (var $z)) (var $v))");
+
+    let beta = Rewrite::new("beta", "(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]");
+    apply_rewrites(&mut eg, &[beta]);
+    assert!(eg.eq(&a1, &b1));
+    assert!(eg.eq(&a2, &b2));
+    assert!(!eg.eq(&a1, &b2));
+    let stats = eg.subst_cache_stats().unwrap();
+    assert_eq!(stats.hits, 0);
+
+    // the same match is found again, so its substitution is answered from the cache.
+    let beta = Rewrite::new("beta", "(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]");
+    apply_rewrites(&mut eg, &[beta]);
+    let stats2 = eg.subst_cache_stats().unwrap();
+    assert_eq!(stats2.misses, stats.misses);
+    assert!(stats2.hits >= 1);
+    assert!(stats2.hit_rate() > 0.0);
+    assert_eq!(EGraph::<Lambda>::default().subst_cache_stats(), None);
+}
+
+#[test]
+fn memo_subst_after_union() {
+    let mut eg: EGraph<Lambda> = EGraph::with_subst_method::<MemoSubst<SynExprSubst>>(());
+    let beta = || Rewrite::new("beta", "(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]");
+    let t1 = eg.add_expr(RecExpr::parse("(lam $z (var $z))").unwrap());
+    let t2 = eg.add_expr(RecExpr::parse("(lam $z (app (var $z) (var $z)))").unwrap());
+    // `t2` has more parents, so that it represents the union of `t1` and `t2` later.
+    eg.add_expr(RecExpr::parse("(app (var $y) (lam $z (app (var $z) (var $z))))").unwrap());
+    eg.add_expr(
+        RecExpr::parse("(app (app (var $y) (var $y)) (lam $z (app (var $z) (var $z))))").unwrap(),
+    );
+    eg.add_expr(RecExpr::parse("(app (lam $x (lam $w (var $x))) (lam $z (var $z)))").unwrap());
+    apply_rewrites(&mut eg, &[beta()]);
+    let misses = eg.subst_cache_stats().unwrap().misses;
+
+    // after the union, the cached substitution is found under the new canonical e-class of its argument.
+    eg.union(&t1, &t2);
+    assert_ne!(eg.find_applied_id(&t1).id, t1.id);
+    apply_rewrites(&mut eg, &[beta()]);
+    assert_eq!(eg.subst_cache_stats().unwrap().misses, misses);
+}
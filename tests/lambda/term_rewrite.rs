@@ -0,0 +1,117 @@
+use crate::*;
+
+fn re(s: &str) -> RecExpr<Lambda> {
+    RecExpr::parse(s).unwrap()
+}
+
+fn let_rules() -> Vec<Rewrite<Lambda>> {
+    parse_rules(
+        "
+        beta: (app (lam $1 ?b) ?t) => (let $1 ?b ?t)
+        let-var-same: (let $1 (var $1) ?t) => ?t
+        let-unused: (let $1 ?b ?t) => ?b where $1 # ?b
+        let-app: (let $1 (app ?a ?b) ?t) => (app (let $1 ?a ?t) (let $1 ?b ?t))
+        let-lam: (let $1 (lam $2 ?b) ?t) => (lam $2 (let $1 ?b ?t)) where $2 # ?t
+        ",
+    )
+    .unwrap()
+}
+
+#[test]
+fn term_alpha_eq() {
+    assert!(alpha_eq(&re("(lam $1 (var $1))"), &re("(lam $2 (var $2))")));
+    assert!(!alpha_eq(
+        &re("(lam $1 (var $1))"),
+        &re("(lam $2 (var $1))")
+    ));
+    assert!(!alpha_eq(&re("(var $1)"), &re("(var $2)")));
+    assert!(alpha_eq(
+        &re("(lam $1 (lam $2 (app (var $1) (var $2))))"),
+        &re("(lam $2 (lam $1 (app (var $2) (var $1))))")
+    ));
+    // the value of a let is not in the scope of its binder.
+    assert!(!alpha_eq(
+        &re("(let $1 (var $1) (var $1))"),
+        &re("(let $2 (var $2) (var $2))")
+    ));
+}
+
+#[test]
+fn term_matching() {
+    let pat = Pattern::parse("(lam $x (app ?f[] (var $x)))").unwrap();
+    let m = match_term(&pat, &re("(lam $7 (app (var $y) (var $7)))"));
+    assert_eq!(m.len(), 1);
+    assert!(alpha_eq(&m[0]["f"], &re("(var $y)")));
+    assert!(match_term(&pat, &re("(lam $7 (app (var $7) (var $7)))")).is_empty());
+
+    let pat = Pattern::parse("(app ?a ?a)").unwrap();
+    assert_eq!(
+        match_term(&pat, &re("(app (lam $1 (var $1)) (lam $2 (var $2)))")).len(),
+        1
+    );
+    assert!(match_term(&pat, &re("(app (var $1) (var $2))")).is_empty());
+
+    let pat = Pattern::parse("(| (app ?a _) (lam $1 ?a))").unwrap();
+    assert_eq!(match_term(&pat, &re("(app (var $1) (var $2))")).len(), 1);
+
+    // operator variables also match e-nodes with slots, and keep their binders.
+    let id_app = Rewrite::<Lambda>::new("id-app", "(?op (app (lam $1 (var $1)) ?a))", "(?op ?a)");
+    let out = id_app
+        .apply_term(&re("(lam $x (app (lam $y (var $y)) (var $x)))"))
+        .unwrap();
+    assert!(alpha_eq(&out, &re("(lam $x (var $x))")), "{out}");
+}
+
+#[test]
+fn term_rewrite_step() {
+    let eta = Rewrite::<Lambda>::new("eta", "(lam $1 (app ?f (var $1)))", "?f where $1 # ?f");
+    let out = eta
+        .apply_term(&re("(lam $x (app (var $y) (var $x)))"))
+        .unwrap();
+    assert!(alpha_eq(&out, &re("(var $y)")));
+    assert!(eta
+        .apply_term(&re("(lam $x (app (var $x) (var $x)))"))
+        .is_none());
+
+    // rules with closure conditions can't be applied to terms.
+    let eta2 = Rewrite::<Lambda>::new_if("eta", "(lam $1 (app ?f (var $1)))", "?f", |_, _| true);
+    assert!(eta2
+        .apply_term(&re("(lam $x (app (var $y) (var $x)))"))
+        .is_none());
+
+    // the substitution doesn't capture `$y`.
+    let beta = Rewrite::<Lambda>::new("beta", "(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]");
+    let t = re("(app (lam $x (lam $y (app (var $x) (var $y)))) (var $y))");
+    let out = rewrite_step(&t, &[beta], Strategy::Outermost).unwrap();
+    assert!(
+        alpha_eq(&out, &re("(lam $z (app (var $y) (var $z)))")),
+        "{out}"
+    );
+
+    let t = re("(app (lam $x (var $x)) (app (lam $y (var $y)) (var $z)))");
+    let outer = rewrite_step(&t, &let_rules(), Strategy::Outermost).unwrap();
+    assert!(
+        alpha_eq(
+            &outer,
+            &re("(let $x (var $x) (app (lam $y (var $y)) (var $z)))")
+        ),
+        "{outer}"
+    );
+    let inner = rewrite_step(&t, &let_rules(), Strategy::Innermost).unwrap();
+    assert!(
+        alpha_eq(
+            &inner,
+            &re("(app (lam $x (var $x)) (let $y (var $y) (var $z)))")
+        ),
+        "{inner}"
+    );
+}
+
+#[test]
+fn term_normalize() {
+    let t = re(&app(app(add(), num(1)), num(2)));
+    let out = normalize(&t, &let_rules(), Strategy::Outermost, 100_000);
+    assert!(rewrite_step(&out, &let_rules(), Strategy::Outermost).is_none());
+    let expected = normalize(&re(&num(3)), &let_rules(), Strategy::Innermost, 100_000);
+    assert!(alpha_eq(&out, &expected), "{out}");
+}
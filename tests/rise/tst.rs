@@ -217,3 +217,48 @@ fn small2() {
     #[cfg(feature = "explanations")]
     println!("{}", eg.explain_equivalence(x1, x4).to_string(&eg));
 }
+
+#[test]
+fn pattern_macro() {
+    fn check(a: Pattern<Rise>, b: &str) {
+        let b = Pattern::parse(b).unwrap();
+        assert!(a == b, "{a} != {b}");
+    }
+    // every pattern is built by `pattern!` and by `Pattern::parse` from the same string.
+    macro_rules! round_trip {
+        ($($s:literal),* $(,)?) => { $(check(pattern!(Rise, $s), $s);)* };
+    }
+    round_trip!(
+        "(app (lam $x ?b) ?t)",
+        "(let $1 (var $1) 3)",
+        "?b[(var $x) := ?t, (var $y) := add]",
+        "?b[(var $x) := (app ?f (var $y)), (var $y) := 3][(var $z) := ?t]",
+        "(lam $x (app ?f[] (var $x)))",
+        "?f[$y, (var $z)]",
+        "(| (?op ?a _) ?a)",
+        "(| (app ?f ?x) (lam $x (var $x)) map)",
+        "(?op (app _ (var $x)))",
+        "  ( app   map\n(var $0) ) ",
+        "f1",
+        "42",
+    );
+
+    // the expansion must not rely on a glob import of the crate.
+    mod no_glob {
+        pub fn pat() -> slotted_egraphs::Pattern<crate::Rise> {
+            slotted_egraphs::pattern!(crate::Rise, "(app (lam $x ?f[$x]) 3)")
+        }
+    }
+    check(no_glob::pat(), "(app (lam $x ?f[$x]) 3)");
+
+    let beta = Rewrite::from_parts(
+        "beta",
+        CompiledPattern::new(pattern!(Rise, "(app (lam $x ?b) ?t)")),
+        pattern!(Rise, "(let $x ?b ?t)"),
+    );
+    let eg: &mut EGraph<Rise> = &mut EGraph::default();
+    let a = eg.add_expr(RecExpr::parse("(app (lam $1 (var $1)) f)").unwrap());
+    apply_rewrites(eg, &[beta]);
+    let b = eg.add_expr(RecExpr::parse("(let $1 (var $1) f)").unwrap());
+    assert!(eg.eq(&a, &b));
+}